@@ -0,0 +1,214 @@
+use crate::{
+	crypto, ckb::{
+		Signer, KeySigner, RemoteSigner
+	}
+};
+use kabletop_ckb_sdk::config::VARS;
+use ckb_crypto::secp::Privkey;
+use zeroize::Zeroizing;
+use bip39::{
+	Mnemonic, Language, Seed
+};
+use hmac::{
+	Hmac, Mac, NewMac
+};
+use sha2::Sha512;
+use serde::{
+	Deserialize, Serialize
+};
+use rand::RngCore;
+use std::sync::{
+	Mutex, Arc
+};
+
+const KEYSTORE_DIR: &str = "keystore";
+const HARDENED: u32 = 0x80000000;
+
+// derivation path of the first ckb receiving key, m/44'/309'/0'/0/0
+const CKB_PATH: [u32; 5] = [44 | HARDENED, 309 | HARDENED, HARDENED, 0, 0];
+
+lazy_static! {
+	// signer of the account switched from keystore, none of it means the account configured in kabletop-ckb-sdk is active
+	static ref ACTIVE: Mutex<Option<Arc<dyn Signer>>> = Mutex::new(None);
+	// the configured key is copied into a zeroizing secret only once
+	static ref CONFIGURED: Arc<dyn Signer> = Arc::new(KeySigner::configured());
+}
+
+// the content of keystore/{pkhash}.json, the private key is encrypted by password
+#[derive(Serialize, Deserialize)]
+struct AccountFile {
+	name:      String,
+	pkhash:    [u8; 20],
+	encrypted: Vec<u8>
+}
+
+#[derive(Clone)]
+pub struct AccountInfo {
+	pub name:    String,
+	pub pkhash:  [u8; 20],
+	pub active:  bool
+}
+
+fn pkhash_of(secret: &[u8; 32]) -> Result<[u8; 20], String> {
+	let pubkey = Privkey::from_slice(secret)
+		.pubkey()
+		.map_err(|err| err.to_string())?;
+	let mut pkhash = [0u8; 20];
+	pkhash.copy_from_slice(&ckb_hash::blake2b_256(pubkey.serialize())[..20]);
+	Ok(pkhash)
+}
+
+// derive the private key of CKB_PATH from bip39 seed in the way of bip32
+fn derive_secret(seed: &[u8]) -> Result<Zeroizing<[u8; 32]>, String> {
+	let hmac_sha512 = |key: &[u8], data: &[u8]| -> Result<Vec<u8>, String> {
+		let mut mac = Hmac::<Sha512>::new_from_slice(key).map_err(|err| err.to_string())?;
+		mac.update(data);
+		Ok(mac.finalize().into_bytes().to_vec())
+	};
+	let master = hmac_sha512(b"Bitcoin seed", seed)?;
+	let mut secret = secp256k1::SecretKey::from_slice(&master[..32]).map_err(|err| err.to_string())?;
+	let mut chain_code = master[32..].to_vec();
+	for &index in CKB_PATH.iter() {
+		let mut data = if index >= HARDENED {
+			[&[0u8][..], &secret[..]].concat()
+		} else {
+			let secp = secp256k1::Secp256k1::signing_only();
+			secp256k1::PublicKey::from_secret_key(&secp, &secret).serialize().to_vec()
+		};
+		data.extend(&index.to_be_bytes());
+		let child = hmac_sha512(&chain_code, &data)?;
+		secret.add_assign(&child[..32]).map_err(|err| err.to_string())?;
+		chain_code = child[32..].to_vec();
+	}
+	let mut value = Zeroizing::new([0u8; 32]);
+	value.copy_from_slice(&secret[..]);
+	Ok(value)
+}
+
+fn store_account(name: String, secret: Zeroizing<[u8; 32]>, password: &str) -> Result<[u8; 20], String> {
+	secp256k1::SecretKey::from_slice(&secret[..]).map_err(|err| err.to_string())?;
+	let pkhash = pkhash_of(&secret)?;
+	let file = AccountFile {
+		name,
+		pkhash,
+		encrypted: crypto::encrypt(password, &secret[..])?
+	};
+	std::fs::create_dir_all(KEYSTORE_DIR).map_err(|err| err.to_string())?;
+	let content = serde_json::to_string_pretty(&file).map_err(|err| err.to_string())?;
+	std::fs::write(format!("{}/{}.json", KEYSTORE_DIR, hex::encode(pkhash)), content).map_err(|err| err.to_string())?;
+	Ok(pkhash)
+}
+
+fn load_account(pkhash: [u8; 20]) -> Result<AccountFile, String> {
+	let path = format!("{}/{}.json", KEYSTORE_DIR, hex::encode(pkhash));
+	let content = std::fs::read_to_string(path.clone()).map_err(|err| format!("{} => {}", err, path))?;
+	serde_json::from_str(content.as_str()).map_err(|err| format!("{} => {}", err, path))
+}
+
+// generate a new account from random mnemonic, return its pkhash and the mnemonic phrase for backup
+pub fn generate_account(name: String, password: &str) -> Result<([u8; 20], String), String> {
+	let mut entropy = [0u8; 16];
+	rand::thread_rng().fill_bytes(&mut entropy);
+	let mnemonic = Mnemonic::from_entropy(&entropy, Language::English).map_err(|err| err.to_string())?;
+	let secret = derive_secret(Seed::new(&mnemonic, "").as_bytes())?;
+	let pkhash = store_account(name, secret, password)?;
+	Ok((pkhash, mnemonic.phrase().to_string()))
+}
+
+// import an account from 32 bytes private key in hex or bip39 mnemonic phrase
+pub fn import_account(name: String, secret: &str, password: &str) -> Result<[u8; 20], String> {
+	let key = secret.trim().trim_start_matches("0x");
+	let secret = if key.len() == 64 && key.chars().all(|c| c.is_ascii_hexdigit()) {
+		let mut value = Zeroizing::new([0u8; 32]);
+		hex::decode_to_slice(key, &mut value[..]).map_err(|err| err.to_string())?;
+		value
+	} else {
+		let mnemonic = Mnemonic::from_phrase(secret.trim(), Language::English).map_err(|err| err.to_string())?;
+		derive_secret(Seed::new(&mnemonic, "").as_bytes())?
+	};
+	store_account(name, secret, password)
+}
+
+// list the configured account and all accounts in keystore
+pub fn list_accounts() -> Result<Vec<AccountInfo>, String> {
+	let active = active_pubhash();
+	let mut accounts = vec![AccountInfo {
+		name:   String::from("default"),
+		pkhash: VARS.common.user_key.pubhash,
+		active: VARS.common.user_key.pubhash == active
+	}];
+	let dir = match std::fs::read_dir(KEYSTORE_DIR) {
+		Ok(dir) => dir,
+		Err(_)  => return Ok(accounts)
+	};
+	for entry in dir {
+		let path = entry.map_err(|err| err.to_string())?.path();
+		if path.extension().map_or(true, |ext| ext != "json") {
+			continue
+		}
+		let content = std::fs::read_to_string(&path).map_err(|err| format!("{} => {:?}", err, path))?;
+		let file: AccountFile = serde_json::from_str(content.as_str()).map_err(|err| format!("{} => {:?}", err, path))?;
+		accounts.push(AccountInfo {
+			name:   file.name,
+			pkhash: file.pkhash,
+			active: file.pkhash == active
+		});
+	}
+	Ok(accounts)
+}
+
+// switch active account to the one in keystore, or back to the configured one if pkhash is none
+pub fn switch_account(pkhash: Option<[u8; 20]>, password: &str) -> Result<(), String> {
+	let pkhash = match pkhash {
+		Some(pkhash) if pkhash != VARS.common.user_key.pubhash => pkhash,
+		_ => {
+			*ACTIVE.lock().unwrap() = None;
+			return Ok(())
+		}
+	};
+	let file = load_account(pkhash)?;
+	let secret = Zeroizing::new(crypto::decrypt(password, &file.encrypted)?);
+	if secret.len() != 32 {
+		return Err(String::from("broken private key in keystore"));
+	}
+	let mut value = Zeroizing::new([0u8; 32]);
+	value.copy_from_slice(&secret);
+	let signer = KeySigner::from_secret(value)?;
+	if signer.pubhash() != pkhash {
+		return Err(format!("private key in keystore mismatches account {}", hex::encode(pkhash)));
+	}
+	*ACTIVE.lock().unwrap() = Some(Arc::new(signer));
+	Ok(())
+}
+
+// hand all signings over to the companion signer listening at address, which only serves those knowing its token, its
+// account becomes active until another account is switched to
+pub fn use_external_signer(address: &str, token: &str) -> Result<[u8; 20], String> {
+	let signer = RemoteSigner::connect(address, token)?;
+	let pkhash = signer.pubhash();
+	*ACTIVE.lock().unwrap() = Some(Arc::new(signer));
+	Ok(pkhash)
+}
+
+pub fn active_pubhash() -> [u8; 20] {
+	match &*ACTIVE.lock().unwrap() {
+		Some(signer) => signer.pubhash(),
+		None         => VARS.common.user_key.pubhash
+	}
+}
+
+// all signings of the active account go through its signer, the private key itself is never handed out
+pub fn active_signer() -> Arc<dyn Signer> {
+	match &*ACTIVE.lock().unwrap() {
+		Some(signer) => signer.clone(),
+		None         => CONFIGURED.clone()
+	}
+}
+
+// the directory of channel caches, the configured account keeps using db for compatibility
+pub fn db_dir() -> String {
+	match &*ACTIVE.lock().unwrap() {
+		Some(signer) => format!("db/accounts/{}", hex::encode(signer.pubhash())),
+		None         => String::from("db")
+	}
+}
\ No newline at end of file
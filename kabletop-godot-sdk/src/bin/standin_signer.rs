@@ -0,0 +1,91 @@
+use kabletop_godot_sdk::ckb::{
+	serve_signer, Signer, KeySigner, ApprovalPolicy
+};
+use zeroize::Zeroizing;
+use std::io::{
+	stdin, stdout, Write
+};
+
+const USAGE: &str = "standin_signer --listen <address> --token <token> --privkey <hex> [--auto-round <max staking ckb>[:<channel>]]... [--approve-all]";
+
+// --auto-round 1000 signs rounds of any channel staking up to 1000 ckb, and 1000:<script hash> only of that channel
+fn parse_policy(value: &str) -> Result<ApprovalPolicy, String> {
+	let mut parts = value.splitn(2, ':');
+	let max_staking_ckb = parts
+		.next()
+		.unwrap()
+		.parse::<u64>()
+		.map_err(|err| format!("invalid staking ckb {} -> {}", value, err))?;
+	let channel = match parts.next() {
+		Some(channel) => {
+			let mut script_hash = [0u8; 32];
+			hex::decode_to_slice(channel.trim_start_matches("0x"), &mut script_hash).map_err(|err| err.to_string())?;
+			Some(script_hash)
+		},
+		None => None
+	};
+	Ok(ApprovalPolicy {
+		channel,
+		max_staking_ckb
+	})
+}
+
+// ask on terminal, a closed stdin declines everything
+fn ask(description: &str) -> bool {
+	print!("{}? [y/N] ", description);
+	let _ = stdout().flush();
+	let mut answer = String::new();
+	match stdin().read_line(&mut answer) {
+		Ok(_)  => answer.trim().eq_ignore_ascii_case("y"),
+		Err(_) => false
+	}
+}
+
+fn run() -> Result<(), String> {
+	let mut address = None;
+	let mut token = None;
+	let mut secret = None;
+	let mut policies = vec![];
+	let mut approve_all = false;
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = || args.next().ok_or(format!("missing value of {}", arg));
+		match arg.as_str() {
+			"--listen"      => address = Some(value()?),
+			"--token"       => token = Some(Zeroizing::new(value()?)),
+			"--privkey"     => {
+				let value = Zeroizing::new(value()?);
+				let mut key = Zeroizing::new([0u8; 32]);
+				hex::decode_to_slice(value.trim_start_matches("0x"), &mut key[..]).map_err(|err| err.to_string())?;
+				secret = Some(key);
+			},
+			"--auto-round"  => policies.push(parse_policy(value()?.as_str())?),
+			"--approve-all" => approve_all = true,
+			_               => return Err(format!("unknown argument {}", arg))
+		}
+	}
+	let (address, token, secret) = match (address, token, secret) {
+		(Some(address), Some(token), Some(secret)) => (address, token, secret),
+		_                                          => return Err(String::from(USAGE))
+	};
+	if token.is_empty() {
+		return Err(String::from("token can't be empty"));
+	}
+	let signer = KeySigner::from_secret(secret)?;
+	println!("signing for {} at {}", hex::encode(signer.pubhash()), address);
+	serve_signer(address.as_str(), token.as_str(), signer, policies, |description| {
+		if approve_all {
+			println!("approve {}", description);
+			true
+		} else {
+			ask(description)
+		}
+	})
+}
+
+fn main() {
+	if let Err(err) = run() {
+		eprintln!("{}", err);
+		std::process::exit(1);
+	}
+}
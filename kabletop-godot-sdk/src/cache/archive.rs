@@ -0,0 +1,142 @@
+use super::channel::{
+	self, ChannelCache
+};
+use crate::account::db_dir;
+use serde::{
+	Deserialize, Serialize
+};
+
+// winner of channels closed by other ways, whose result can't be told from the close transaction
+pub const UNKNOWN_WINNER: u8 = 0;
+
+// archived caches are kept under the db directory of active account
+pub fn archive_dir() -> String {
+	format!("{}/archive", db_dir())
+}
+
+// a brief record of one closed kabletop channel, which is kept in the archive index
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MatchRecord {
+	pub script_hash:     [u8; 32],
+	pub close_hash:      [u8; 32],
+	pub opponent_pkhash: [u8; 20],
+	pub user_type:       u8,
+	pub winner:          u8,
+	pub staking_ckb:     u64,
+	pub bet_ckb:         u64,
+	pub round_count:     usize,
+	pub open_timestamp:  u64,
+	pub close_timestamp: u64
+}
+
+impl MatchRecord {
+	pub fn is_winner(&self) -> bool {
+		self.winner == self.user_type
+	}
+
+	pub fn is_unknown(&self) -> bool {
+		self.winner == UNKNOWN_WINNER
+	}
+
+	// ckb won (positive) or lost (negative) in this match, in shannons, and nothing if the result is unknown
+	pub fn ckb_delta(&self) -> i64 {
+		if self.is_unknown() {
+			0
+		} else if self.is_winner() {
+			self.bet_ckb as i64
+		} else {
+			-(self.bet_ckb as i64)
+		}
+	}
+
+	// seconds from channel opened to channel closed
+	pub fn duration(&self) -> u64 {
+		std::cmp::max(self.open_timestamp, self.close_timestamp) - self.open_timestamp
+	}
+}
+
+// conditions to pick match records out from the archive index, empty fields mean no limitation
+#[derive(Default)]
+pub struct MatchFilter {
+	pub opponent_pkhash: Option<[u8; 20]>,
+	pub is_winner:       Option<bool>,
+	pub since:           Option<u64>,
+	pub until:           Option<u64>,
+	pub limit:           Option<usize>
+}
+
+impl MatchFilter {
+	fn matches(&self, record: &MatchRecord) -> bool {
+		self.opponent_pkhash.map_or(true, |pkhash| pkhash == record.opponent_pkhash)
+			&& self.is_winner.map_or(true, |is_winner| !record.is_unknown() && is_winner == record.is_winner())
+			&& self.since.map_or(true, |since| record.close_timestamp >= since)
+			&& self.until.map_or(true, |until| record.close_timestamp <= until)
+	}
+}
+
+fn load_index() -> Result<Vec<MatchRecord>, String> {
+	let path = format!("{}/index.json", archive_dir());
+	match std::fs::read_to_string(path.clone()) {
+		Ok(content) => serde_json::from_str(content.as_str()).map_err(|err| format!("{} => {}", err, path)),
+		Err(_)      => Ok(vec![])
+	}
+}
+
+fn save_index(records: &[MatchRecord]) -> Result<(), String> {
+	let content = serde_json::to_string_pretty(records).map_err(|err| err.to_string())?;
+	std::fs::write(format!("{}/index.json", archive_dir()), content).map_err(|err| err.to_string())?;
+	Ok(())
+}
+
+// move the current channel cache into archive store and append its record into the archive index
+pub fn archive(winner: u8, close_hash: [u8; 32]) -> Result<MatchRecord, String> {
+	archive_at(winner, close_hash, channel::timestamp())
+}
+
+// the same as archive, but the channel was closed at close_timestamp rather than now
+pub fn archive_at(winner: u8, close_hash: [u8; 32], close_timestamp: u64) -> Result<MatchRecord, String> {
+	let store = channel::get_clone();
+	let name = hex::encode(store.script_hash);
+	std::fs::create_dir_all(archive_dir()).map_err(|err| err.to_string())?;
+	channel::store_to(format!("{}/{}.json", archive_dir(), name), &ChannelCache {
+		winner, ..store.clone()
+	})?;
+	let record = MatchRecord {
+		script_hash:     store.script_hash,
+		close_hash,
+		opponent_pkhash: store.opponent_pkhash,
+		user_type:       store.user_type,
+		winner,
+		staking_ckb:     store.staking_ckb,
+		bet_ckb:         store.bet_ckb,
+		round_count:     store.signed_rounds.len(),
+		open_timestamp:  store.open_timestamp,
+		close_timestamp
+	};
+	let mut records = load_index()?
+		.into_iter()
+		.filter(|value| value.script_hash != record.script_hash)
+		.collect::<Vec<_>>();
+	records.push(record.clone());
+	save_index(&records)?;
+	let _ = std::fs::remove_file(format!("{}/{}.json", db_dir(), name));
+	Ok(record)
+}
+
+// collect archived match records from the newest to the oldest
+pub fn match_history(filter: MatchFilter) -> Result<Vec<MatchRecord>, String> {
+	let mut records = load_index()?
+		.into_iter()
+		.filter(|record| filter.matches(record))
+		.collect::<Vec<_>>();
+	records.sort_by(|a, b| b.close_timestamp.cmp(&a.close_timestamp));
+	if let Some(limit) = filter.limit {
+		records.truncate(limit);
+	}
+	Ok(records)
+}
+
+// replace the current channel cache with an archived one
+pub fn recover_archived(name: String) -> Result<ChannelCache, String> {
+	channel::recover_from(format!("{}/{}.json", archive_dir(), name))
+}
@@ -0,0 +1,95 @@
+use super::{
+	verify::verify_channel, channel::{
+		self, ChannelCache
+	}
+};
+use crate::{
+	crypto, account::{
+		active_pubhash, db_dir
+	}
+};
+use serde::{
+	Deserialize, Serialize
+};
+
+// a signed collection of all in-flight channel caches, which is encrypted before writing to disk
+#[derive(Serialize, Deserialize)]
+struct BackupBundle {
+	caches:    Vec<ChannelCache>,
+	signer:    [u8; 20],
+	signature: Vec<u8>
+}
+
+// the result of merging backup bundle into local db directory
+#[derive(Default)]
+pub struct RestoreReport {
+	pub restored: Vec<[u8; 32]>,
+	pub skipped:  Vec<[u8; 32]>,
+	pub rejected: Vec<([u8; 32], Vec<String>)>
+}
+
+fn inflight_caches() -> Result<Vec<ChannelCache>, String> {
+	let db = std::fs::read_dir(db_dir()).map_err(|_| format!("can't open {:?}/{} directory", std::env::current_dir(), db_dir()))?;
+	let mut caches = vec![];
+	for path in db {
+		let path = path.map_err(|err| err.to_string())?.path();
+		let is_cache = path
+			.file_name()
+			.and_then(|name| name.to_str())
+			.and_then(|name| name.strip_suffix(".json"))
+			.map_or(false, channel::is_cache_name);
+		if path.is_file() && is_cache {
+			caches.push(channel::load_from(path.to_string_lossy().to_string())?);
+		}
+	}
+	Ok(caches)
+}
+
+// pack all in-flight channel caches into one bundle signed by user and encrypted by password
+pub fn backup(path: String, password: String) -> Result<usize, String> {
+	let caches = inflight_caches()?;
+	let content = serde_json::to_vec(&caches).map_err(|err| err.to_string())?;
+	let bundle = BackupBundle {
+		signature: crypto::sign(&content)?,
+		signer:    active_pubhash(),
+		caches
+	};
+	let content = serde_json::to_vec(&bundle).map_err(|err| err.to_string())?;
+	std::fs::write(path, crypto::encrypt(password.as_str(), &content)?).map_err(|err| err.to_string())?;
+	Ok(bundle.caches.len())
+}
+
+// merge verified channel caches in backup bundle into db directory, the one with more signed rounds will be kept
+pub fn restore(path: String, password: String) -> Result<RestoreReport, String> {
+	let content = std::fs::read(path.clone()).map_err(|err| format!("{} => {}", err, path))?;
+	let content = crypto::decrypt(password.as_str(), &content)?;
+	let bundle: BackupBundle = serde_json::from_slice(&content).map_err(|err| err.to_string())?;
+	let content = serde_json::to_vec(&bundle.caches).map_err(|err| err.to_string())?;
+	let signer = crypto::recover_signer(&content, &bundle.signature)?;
+	if signer != bundle.signer || signer != active_pubhash() {
+		return Err(format!("backup is signed by {}, not the current user", hex::encode(signer)));
+	}
+	std::fs::create_dir_all(db_dir()).map_err(|err| err.to_string())?;
+	let mut report = RestoreReport::default();
+	for cache in bundle.caches {
+		// a signed bundle may still carry broken caches, which must not replace local ones
+		let issues = match verify_channel(&cache, hex::encode(cache.script_hash).as_str()) {
+			Ok(report) => report.issues,
+			Err(err)   => vec![err]
+		};
+		if !issues.is_empty() {
+			report.rejected.push((cache.script_hash, issues));
+			continue
+		}
+		let path = format!("{}/{}.json", db_dir(), hex::encode(cache.script_hash));
+		if let Ok(local) = channel::load_from(path.clone()) {
+			if local.signed_rounds.len() >= cache.signed_rounds.len() {
+				report.skipped.push(cache.script_hash);
+				continue
+			}
+		}
+		channel::store_to(path, &cache)?;
+		report.restored.push(cache.script_hash);
+	}
+	Ok(report)
+}
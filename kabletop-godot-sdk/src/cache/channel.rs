@@ -1,15 +1,20 @@
 use ckb_crypto::secp::Signature;
-use std::sync::Mutex;
+use std::{
+	sync::Mutex, time::{
+		SystemTime, UNIX_EPOCH
+	}
+};
 use molecule::prelude::Entity;
-use kabletop_ckb_sdk::{
-	config::VARS, ckb::transaction::{
-		helper::fee as str_to_capacity, channel::{
-			interact::make_round, protocol::{
-				Round, Args
-			}
+use kabletop_ckb_sdk::ckb::transaction::{
+	helper::fee as str_to_capacity, channel::{
+		interact::make_round, protocol::{
+			Round, Args
 		}
 	}
 };
+use crate::account::{
+	active_pubhash, db_dir
+};
 use serde::{
     Deserialize, Serialize
 };
@@ -37,11 +42,20 @@ pub struct ChannelCache {
 	pub opponent_nfts:   Vec<[u8; 20]>,
 	pub user_pkhash:     [u8; 20],
 	pub opponent_pkhash: [u8; 20],
+	#[serde(default)]
+	pub open_timestamp:  u64,
+	// fee rate which user pays for opening channel, the default one of ckb-sdk is used if not set
+	#[serde(default)]
+	pub fee_rate:        Option<u64>,
 
 	// for kabletop round
 	pub winner:           u8,
 	pub user_type:        u8,
 	pub opponent_type:    u8,
+	#[serde(default)]
+	pub round_index:      usize,
+	#[serde(default)]
+	pub round_owner:      u8,
 	pub round_operations: Vec<String>,
 	pub signed_rounds:    Vec<(Vec<u8>, Vec<u8>)>
 }
@@ -58,17 +72,57 @@ impl Default for ChannelCache {
 			max_nfts_count:   40,
 			user_nfts:        vec![],
 			opponent_nfts:    vec![],
-			user_pkhash:      VARS.common.user_key.pubhash.clone(),
+			user_pkhash:      active_pubhash(),
 			opponent_pkhash:  [0u8; 20],
+			open_timestamp:   0,
+			fee_rate:         None,
 			winner:           0,
 			user_type:        0,
 			opponent_type:    0,
+			round_index:      1,
+			round_owner:      1,
 			round_operations: vec![],
 			signed_rounds:    vec![]
 		}
 	}
 }
 
+impl ChannelCache {
+	pub fn kabletop_signed_rounds(&self) -> Result<Vec<(Round, Signature)>, String> {
+		self.signed_rounds
+			.iter()
+			.map(|(round, signature)| {
+				match Round::from_slice(round.as_slice()) {
+					Ok(round) => match Signature::from_slice(signature.as_slice()) {
+						Ok(signature) => Ok((round, signature)),
+						Err(error)    => Err(error.to_string())
+					},
+					Err(error) => Err(error.to_string())
+				}
+			})
+			.collect::<Result<Vec<_>, _>>()
+	}
+
+	pub fn kabletop_args(&self) -> Result<Args, String> {
+		Args::from_slice(&self.script_args).map_err(|err| err.to_string())
+	}
+
+	// caches persisted before round tracking have no round index, so derive it from the signed rounds
+	fn complete_round_tracking(&mut self) {
+		if self.round_index > 0 {
+			return
+		}
+		self.round_index = self.signed_rounds.len() + 1;
+		self.round_owner = match self.signed_rounds.last() {
+			Some((round, _)) => match Round::from_slice(round.as_slice()) {
+				Ok(round) if u8::from(round.user_type()) == 1 => 2,
+				_                                             => 1
+			},
+			None => 1
+		};
+	}
+}
+
 pub fn init(player_type: PLAYER_TYPE) {
 	let mut channel = CHANNEL_CACHE.lock().unwrap();
 	*channel = ChannelCache::default();
@@ -95,12 +149,18 @@ pub fn set_staking_and_bet_ckb(staking: u64, bet: u64) {
 	channel.bet_ckb = str_to_capacity(bet.to_string().as_str()).as_u64();
 }
 
+pub fn set_fee_rate(fee_rate: Option<u64>) {
+	let mut channel = CHANNEL_CACHE.lock().unwrap();
+	channel.fee_rate = fee_rate;
+}
+
 pub fn set_channel_verification(channel_hash: [u8; 32], script_hash: [u8; 32], script_args: Vec<u8>, capacity: u64) {
 	let mut channel = CHANNEL_CACHE.lock().unwrap();
 	channel.channel_hash = channel_hash;
 	channel.script_hash = script_hash;
 	channel.script_args = script_args;
 	channel.capacity = capacity;
+	channel.open_timestamp = timestamp();
 }
 
 pub fn set_winner(winner: u8) {
@@ -128,6 +188,8 @@ pub fn commit_user_round(signature: Signature) {
 	let round = make_round(channel.user_type, channel.round_operations.clone());
 	channel.signed_rounds.push((round.as_slice().to_vec(), signature.serialize()));
 	channel.round_operations = vec![];
+	channel.round_index += 1;
+	channel.round_owner = channel.opponent_type;
 }
 
 pub fn commit_opponent_round(signature: Signature) {
@@ -135,55 +197,97 @@ pub fn commit_opponent_round(signature: Signature) {
 	let round = make_round(channel.opponent_type, channel.round_operations.clone());
 	channel.signed_rounds.push((round.as_slice().to_vec(), signature.serialize()));
 	channel.round_operations = vec![];
+	channel.round_index += 1;
+	channel.round_owner = channel.user_type;
+}
+
+// an empty round of user is never signed, so the round passes to opponent without being committed
+pub fn hand_round_to_opponent() {
+	let mut channel = CHANNEL_CACHE.lock().unwrap();
+	if channel.round_owner == channel.user_type && channel.round_operations.is_empty() {
+		channel.round_owner = channel.opponent_type;
+	}
 }
 
-pub fn commit_user_operation(operation: String) {
+pub fn commit_user_operation(operation: String) -> Result<(), String> {
 	let mut channel = CHANNEL_CACHE.lock().unwrap();
+	if channel.round_owner != channel.user_type {
+		return Err(format!("round {} is owned by opponent, user can't operate", channel.round_index));
+	}
 	channel.round_operations.push(operation);
+	Ok(())
 }
 
-pub fn commit_opponent_operation(operation: String) {
+pub fn commit_opponent_operation(operation: String) -> Result<(), String> {
 	let mut channel = CHANNEL_CACHE.lock().unwrap();
+	if channel.round_owner != channel.opponent_type {
+		return Err(format!("round {} is owned by user, opponent can't operate", channel.round_index));
+	}
 	channel.round_operations.push(operation);
+	Ok(())
 }
 
 pub fn get_kabletop_signed_rounds() -> Result<Vec<(Round, Signature)>, String> {
-	get_clone()
-		.signed_rounds
-		.into_iter()
-		.map(|(round, signature)| {
-			match Round::from_slice(round.as_slice()) {
-				Ok(round) => match Signature::from_slice(signature.as_slice()) {
-					Ok(signature) => Ok((round, signature)),
-					Err(error)    => Err(error.to_string())
-				},
-				Err(error) => Err(error.to_string())
-			}
-		})
-		.collect::<Result<Vec<_>, _>>()
+	get_clone().kabletop_signed_rounds()
 }
 
 pub fn get_kabletop_args() -> Result<Args, String> {
-	Ok(Args::from_slice(&get_clone().script_args).map_err(|err| err.to_string())?)
+	get_clone().kabletop_args()
 }
 
 pub fn persist(name: String) -> Result<(), String> {
-	let path = format!("db/{}.json", name);
-	let content = serde_json::to_string_pretty(&get_clone()).map_err(|err| err.to_string())?;
+	store_to(format!("{}/{}.json", db_dir(), name), &get_clone())
+}
+
+// write specified channel cache into json file in the same format of persist
+pub fn store_to(path: String, channel: &ChannelCache) -> Result<(), String> {
+	if let Some(dir) = std::path::Path::new(&path).parent() {
+		std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+	}
+	let content = serde_json::to_string_pretty(channel).map_err(|err| err.to_string())?;
 	std::fs::write(path, content).map_err(|err| err.to_string())?;
 	Ok(())
 }
 
 pub fn recover(name: String) -> Result<ChannelCache, String> {
-	let path = format!("db/{}.json", name);
+	recover_from(format!("{}/{}.json", db_dir(), name))
+}
+
+// channel caches are named by hex script hash, other json files in db directory aren't caches
+pub fn is_cache_name(name: &str) -> bool {
+	name.len() == 64 && name.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+// replace the current channel cache with the one stored in specified json file
+pub fn recover_from(path: String) -> Result<ChannelCache, String> {
+	let channel = load_from(path)?;
+	*CHANNEL_CACHE.lock().unwrap() = channel;
+	Ok(get_clone())
+}
+
+// read channel cache from specified json file without touching the current one
+pub fn load_from(path: String) -> Result<ChannelCache, String> {
 	let content = std::fs::read_to_string(path.clone())
 		.map_err(|err| format!("{} => {}", err, path))?;
-	let channel: ChannelCache = serde_json::from_str(content.as_str())
+	let mut channel: ChannelCache = serde_json::from_str(content.as_str())
 		.map_err(|err| err.to_string())?;
-	*CHANNEL_CACHE.lock().unwrap() = channel;
-	Ok(get_clone())
+	channel.complete_round_tracking();
+	Ok(channel)
+}
+
+// seconds elapsed since unix epoch, used to mark the lifetime of channels
+pub fn timestamp() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|duration| duration.as_secs())
+		.unwrap_or(0)
 }
 
 pub fn get_clone() -> ChannelCache {
 	CHANNEL_CACHE.lock().unwrap().clone()
 }
+
+// put specified channel cache in place of the current one, and return the replaced one
+pub fn replace(channel: ChannelCache) -> ChannelCache {
+	std::mem::replace(&mut *CHANNEL_CACHE.lock().unwrap(), channel)
+}
@@ -1,5 +1,13 @@
 mod channel;
 mod godot;
+mod archive;
+mod replay;
+mod verify;
+mod backup;
 
 pub use channel::*;
-pub use godot::*;
\ No newline at end of file
+pub use godot::*;
+pub use archive::*;
+pub use replay::*;
+pub use verify::*;
+pub use backup::*;
\ No newline at end of file
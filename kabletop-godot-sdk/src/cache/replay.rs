@@ -0,0 +1,86 @@
+use super::{
+	archive::archive_dir, verify::verify_signed_rounds, channel::{
+		self, ChannelCache
+	}
+};
+use crate::account::db_dir;
+use kabletop_ckb_sdk::{
+	config::VARS, ckb::transaction::{
+		helper::kabletop_script, channel::protocol::{
+			Round, Args
+		}
+	}
+};
+use ckb_types::prelude::Unpack;
+use ckb_crypto::secp::Signature;
+use molecule::prelude::Entity;
+use serde::{
+	Deserialize, Serialize
+};
+
+const REPLAY_VERSION: u8 = 1;
+
+// a portable match record which carries everything a spectator needs to verify and replay the game
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReplayFile {
+	pub version:         u8,
+	pub script_args:     Vec<u8>,
+	pub signed_rounds:   Vec<(Vec<u8>, Vec<u8>)>,
+	pub lua_code_hashes: Vec<[u8; 32]>,
+	// claimed by the exporter only, replay the rounds to get a verified winner
+	pub winner:          u8
+}
+
+impl ReplayFile {
+	pub fn kabletop_args(&self) -> Result<Args, String> {
+		Args::from_slice(&self.script_args).map_err(|err| err.to_string())
+	}
+
+	pub fn kabletop_script_hash(&self) -> [u8; 32] {
+		kabletop_script(self.script_args.clone()).calc_script_hash().unpack()
+	}
+
+	pub fn kabletop_signed_rounds(&self) -> Result<Vec<(Round, Signature)>, String> {
+		ChannelCache {
+			signed_rounds: self.signed_rounds.clone(), ..ChannelCache::default()
+		}.kabletop_signed_rounds()
+	}
+}
+
+// bundle the in-flight or archived channel cache of specified script_hash into a replay file
+pub fn export_replay(name: String, path: String) -> Result<(), String> {
+	let store = channel::load_from(format!("{}/{}.json", db_dir(), name))
+		.or_else(|_| channel::load_from(format!("{}/{}.json", archive_dir(), name)))?;
+	let replay = ReplayFile {
+		version:         REPLAY_VERSION,
+		script_args:     store.script_args,
+		signed_rounds:   store.signed_rounds,
+		lua_code_hashes: VARS.luacodes.iter().map(|value| value.data_hash.clone()).collect(),
+		winner:          store.winner
+	};
+	let content = serde_json::to_string_pretty(&replay).map_err(|err| err.to_string())?;
+	std::fs::write(path, content).map_err(|err| err.to_string())?;
+	Ok(())
+}
+
+// load a replay file and check every round signature is signed by the opponent of round owner
+pub fn import_replay(path: String) -> Result<ReplayFile, String> {
+	let content = std::fs::read_to_string(path.clone())
+		.map_err(|err| format!("{} => {}", err, path))?;
+	let replay: ReplayFile = serde_json::from_str(content.as_str())
+		.map_err(|err| err.to_string())?;
+	if replay.version != REPLAY_VERSION {
+		return Err(format!("unsupported replay version {}", replay.version));
+	}
+	let native_hashes = VARS.luacodes.iter().map(|value| value.data_hash.clone()).collect::<Vec<_>>();
+	if replay.lua_code_hashes != native_hashes {
+		return Err(String::from("replay and native lua codes are mismatched"));
+	}
+	let failures = verify_signed_rounds(
+		replay.kabletop_script_hash(), &replay.kabletop_args()?, &replay.kabletop_signed_rounds()?
+	);
+	if let Some((_, issue)) = failures.into_iter().next() {
+		return Err(issue);
+	}
+	Ok(replay)
+}
@@ -0,0 +1,143 @@
+use super::channel::{
+	self, ChannelCache
+};
+use crate::{
+	account::db_dir, ckb::get_kabletop_challenge_data
+};
+use kabletop_ckb_sdk::ckb::transaction::{
+	helper::kabletop_script, channel::{
+		interact::check_channel_round, protocol::{
+			Round, Args
+		}
+	}
+};
+use ckb_types::{
+	H256, prelude::Unpack
+};
+use ckb_crypto::secp::Signature;
+use molecule::prelude::Entity;
+use futures::executor::block_on;
+
+// the result of checking one channel cache file, each failure is described in issues
+#[derive(Default)]
+pub struct CacheReport {
+	pub script_hash_matched: bool,
+	pub pkhash_matched:      bool,
+	pub round_count:         usize,
+	pub invalid_signatures:  Vec<usize>,
+	pub invalid_operations:  Vec<usize>,
+	pub channel_alive:       bool,
+	pub challenger:          Option<u8>,
+	pub issues:              Vec<String>
+}
+
+impl CacheReport {
+	pub fn is_valid(&self) -> bool {
+		self.issues.is_empty()
+	}
+}
+
+// one round is signed over blake2b of the previous signature (script_hash for the first round) and the round itself,
+// which mirrors sign_channel_round of kabletop-ckb-sdk, so rounds can be checked one by one on the running prefix,
+// tests/signer.rs pins this digest to rounds signed by kabletop-ckb-sdk
+pub fn recover_round_signer(last: &[u8], round: &Round, signature: &Signature) -> Option<[u8; 20]> {
+	let message = H256::from(ckb_hash::blake2b_256([last, round.as_slice()].concat()));
+	let pubkey = signature.recover(&message).ok()?;
+	let mut pkhash = [0u8; 20];
+	pkhash.copy_from_slice(&ckb_hash::blake2b_256(pubkey.serialize())[..20]);
+	Some(pkhash)
+}
+
+// check signature of each round is signed by the opponent of round owner, and return the failed rounds
+pub fn verify_signed_rounds(
+	script_hash: [u8; 32], lock_args: &Args, signed_rounds: &[(Round, Signature)]
+) -> Vec<(usize, String)> {
+	let mut failures = vec![];
+	let mut last = script_hash.to_vec();
+	for i in 0..signed_rounds.len() {
+		let (round, signature) = &signed_rounds[i];
+		let previous = std::mem::replace(&mut last, signature.serialize());
+		let round_owner = u8::from(signed_rounds[i].0.user_type());
+		let signer_pkhash: [u8; 20] = match round_owner {
+			1 => lock_args.user2_pkhash().into(),
+			2 => lock_args.user1_pkhash().into(),
+			_ => {
+				failures.push((i + 1, format!("invalid owner {} of round {}", round_owner, i + 1)));
+				continue
+			}
+		};
+		if recover_round_signer(&previous, round, signature) == Some(signer_pkhash) {
+			continue
+		}
+		// only a round which fails the running check pays for checking its whole prefix by kabletop-ckb-sdk
+		match check_channel_round(script_hash.into(), signed_rounds[..=i].to_vec(), signer_pkhash) {
+			Ok(true)  => (),
+			Ok(false) => failures.push((i + 1, format!("signature of round {} not match pkhash {}", i + 1, hex::encode(signer_pkhash)))),
+			Err(err)  => failures.push((i + 1, format!("check_channel_round of round {} -> {}", i + 1, err)))
+		}
+	}
+	failures
+}
+
+// check the consistency of cache file db/{name}.json and compare it with the kabletop cell on chain
+pub fn verify_cache(name: String) -> Result<CacheReport, String> {
+	let store = channel::load_from(format!("{}/{}.json", db_dir(), name))?;
+	let mut report = verify_channel(&store, name.as_str())?;
+
+	// the kabletop cell should still be alive, and the challenge data in it should be readable
+	let (alive, challenge) = block_on(get_kabletop_challenge_data(store.script_args.clone()))?;
+	report.channel_alive = alive;
+	if !alive {
+		report.issues.push(String::from("no live kabletop cell found on chain"));
+	}
+	if let Some(challenge) = challenge {
+		let challenger = u8::from(challenge.challenger());
+		let operations: Vec<Vec<u8>> = challenge.operations().into();
+		if operations.into_iter().any(|bytes| String::from_utf8(bytes).is_err()) {
+			report.issues.push(String::from("operations of on-chain challenge aren't utf8 encoded"));
+		}
+		report.challenger = Some(challenger);
+	}
+	Ok(report)
+}
+
+// check the consistency of one channel cache named by its script_hash without touching chain
+pub fn verify_channel(store: &ChannelCache, name: &str) -> Result<CacheReport, String> {
+	let mut report = CacheReport::default();
+
+	// script_hash must be the hash of kabletop lock_script which is made from script_args
+	let script_hash: [u8; 32] = kabletop_script(store.script_args.clone()).calc_script_hash().unpack();
+	report.script_hash_matched = script_hash == store.script_hash && hex::encode(script_hash) == name;
+	if !report.script_hash_matched {
+		report.issues.push(format!("script_hash {} mismatches the hash of script_args", hex::encode(store.script_hash)));
+	}
+	let lock_args = store.kabletop_args()?;
+	let (user_pkhash, opponent_pkhash): ([u8; 20], [u8; 20]) = match store.user_type {
+		1 => (lock_args.user1_pkhash().into(), lock_args.user2_pkhash().into()),
+		2 => (lock_args.user2_pkhash().into(), lock_args.user1_pkhash().into()),
+		_ => return Err(format!("invalid user_type {}", store.user_type))
+	};
+	report.pkhash_matched = user_pkhash == store.user_pkhash && opponent_pkhash == store.opponent_pkhash;
+	if !report.pkhash_matched {
+		report.issues.push(String::from("user or opponent pkhash mismatches script_args"));
+	}
+
+	// every stored round should be signed by the opposite side of its owner and carry utf8 operations
+	let signed_rounds = store.kabletop_signed_rounds()?;
+	report.round_count = signed_rounds.len();
+	if store.round_index != signed_rounds.len() + 1 {
+		report.issues.push(format!("round_index {} mismatches {} signed rounds", store.round_index, signed_rounds.len()));
+	}
+	for (round, issue) in verify_signed_rounds(script_hash, &lock_args, &signed_rounds) {
+		report.invalid_signatures.push(round);
+		report.issues.push(issue);
+	}
+	for (i, (round, _)) in signed_rounds.iter().enumerate() {
+		let operations: Vec<Vec<u8>> = round.operations().into();
+		if operations.into_iter().any(|bytes| String::from_utf8(bytes).is_err()) {
+			report.invalid_operations.push(i + 1);
+			report.issues.push(format!("operations of round {} aren't utf8 encoded", i + 1));
+		}
+	}
+	Ok(report)
+}
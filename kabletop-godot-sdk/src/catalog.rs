@@ -0,0 +1,77 @@
+use kabletop_ckb_sdk::ckb::wallet::keystore;
+use serde::{
+	Deserialize, Serialize
+};
+use std::{
+	sync::Mutex, collections::HashMap
+};
+
+pub const DEFAULT_CATALOG: &str = "catalog.json";
+
+lazy_static! {
+	static ref CATALOG: Mutex<Option<Catalog>> = Mutex::new(None);
+}
+
+// display metadata of one nft, the hash is the blake160 stored in nft cells
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct NftInfo {
+	#[serde(default)]
+	pub hash:             String,
+	pub name:             String,
+	#[serde(default)]
+	pub rarity:           String,
+	#[serde(default)]
+	pub image:            String,
+	#[serde(default)]
+	pub attributes:       HashMap<String, String>,
+	// the catalog claims to be made for the configured composer, which isn't checked against nft cells on chain
+	#[serde(skip)]
+	pub composer_matches: bool
+}
+
+// the local catalog file which maps nft hashes of one composer to their metadata
+#[derive(Serialize, Deserialize)]
+struct Catalog {
+	#[serde(default)]
+	composer: String,
+	nfts:     HashMap<String, NftInfo>
+}
+
+// load catalog from json file, entries are marked if the catalog declares the configured composer
+pub fn load_catalog(path: String) -> Result<usize, String> {
+	let content = std::fs::read_to_string(path.clone()).map_err(|err| format!("{} => {}", err, path))?;
+	let mut catalog: Catalog = serde_json::from_str(content.as_str()).map_err(|err| format!("{} => {}", err, path))?;
+	let composer_matches = catalog.composer.trim_start_matches("0x") == hex::encode(keystore::COMPOSER_PUBHASH.to_vec());
+	let mut nfts = HashMap::new();
+	for (hash, mut info) in catalog.nfts {
+		let hash = hash.trim_start_matches("0x").to_lowercase();
+		if hash.len() != 40 || hex::decode(&hash).is_err() {
+			return Err(format!("invalid nft hash {} in catalog {}", hash, path));
+		}
+		info.hash = hash.clone();
+		info.composer_matches = composer_matches;
+		nfts.insert(hash, info);
+	}
+	catalog.nfts = nfts;
+	let count = catalog.nfts.len();
+	*CATALOG.lock().unwrap() = Some(catalog);
+	Ok(count)
+}
+
+// resolve nft hash into its metadata, the default catalog file is loaded at the first time
+pub fn nft_info(hash: &str) -> Option<NftInfo> {
+	if CATALOG.lock().unwrap().is_none() {
+		if let Err(err) = load_catalog(String::from(DEFAULT_CATALOG)) {
+			println!("load_catalog error: {}", err);
+			*CATALOG.lock().unwrap() = Some(Catalog {
+				composer: String::new(),
+				nfts:     HashMap::new()
+			});
+		}
+	}
+	CATALOG
+		.lock()
+		.unwrap()
+		.as_ref()
+		.and_then(|catalog| catalog.nfts.get(&hash.trim_start_matches("0x").to_lowercase()).cloned())
+}
\ No newline at end of file
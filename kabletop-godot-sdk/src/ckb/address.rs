@@ -0,0 +1,144 @@
+use super::rpc;
+use crate::account::active_pubhash;
+use kabletop_ckb_sdk::ckb::transaction::helper::{
+	sighash_script, blake160_to_byte20
+};
+use ckb_types::{
+	bytes::Bytes, core::ScriptHashType, prelude::*, packed::{
+		Script, Byte32
+	}
+};
+use bech32::{
+	FromBase32, ToBase32, Variant
+};
+use std::sync::Mutex;
+
+const SHORT_FORMAT: u8 = 0x01;
+const FULL_DATA_FORMAT: u8 = 0x02;
+const FULL_TYPE_FORMAT: u8 = 0x04;
+const FULL_FORMAT: u8 = 0x00;
+const SIGHASH_CODE_INDEX: u8 = 0x00;
+
+lazy_static! {
+	static ref NETWORK: Mutex<Option<Network>> = Mutex::new(None);
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Network {
+	Mainnet,
+	Testnet
+}
+
+impl Network {
+	pub fn prefix(&self) -> &'static str {
+		match self {
+			Network::Mainnet => "ckb",
+			Network::Testnet => "ckt"
+		}
+	}
+}
+
+// the network of connected ckb node, which is asked from node until it's answered
+pub fn network() -> Result<Network, String> {
+	if let Some(network) = resolved_network() {
+		return Ok(network);
+	}
+	// the lock isn't held across rpc, so callers don't queue up behind a slow node
+	let network = match rpc::get_chain_name()?.as_str() {
+		"ckb" => Network::Mainnet,
+		_     => Network::Testnet
+	};
+	*NETWORK.lock().unwrap() = Some(network);
+	Ok(network)
+}
+
+// the network which has been answered by ckb node, None if it's never asked successfully
+pub fn resolved_network() -> Option<Network> {
+	*NETWORK.lock().unwrap()
+}
+
+fn full_script(code_hash: &[u8], hash_type: ScriptHashType, args: &[u8]) -> Result<Script, String> {
+	let code_hash = Byte32::from_slice(code_hash).map_err(|err| err.to_string())?;
+	Ok(Script::new_builder()
+		.code_hash(code_hash)
+		.hash_type(hash_type.into())
+		.args(Bytes::from(args.to_vec()).pack())
+		.build())
+}
+
+// parse short or full ckb address into lock_script, the address prefix must match the connected network
+pub fn parse_address(address: &str) -> Result<Script, String> {
+	let (prefix, data, variant) = bech32::decode(address).map_err(|err| format!("{} => {}", err, address))?;
+	let network = network()?;
+	if prefix != network.prefix() {
+		return Err(format!("address prefix {} mismatches {} of {:?}", prefix, network.prefix(), network));
+	}
+	let payload = Vec::<u8>::from_base32(&data).map_err(|err| err.to_string())?;
+	let invalid = || format!("invalid payload of address {}", address);
+	match (payload.first(), variant) {
+		(Some(&SHORT_FORMAT), Variant::Bech32) => {
+			if payload.len() != 22 || payload[1] != SIGHASH_CODE_INDEX {
+				return Err(invalid());
+			}
+			Ok(sighash_script(&payload[2..].to_vec()))
+		},
+		(Some(&FULL_DATA_FORMAT), Variant::Bech32) | (Some(&FULL_TYPE_FORMAT), Variant::Bech32) => {
+			if payload.len() < 33 {
+				return Err(invalid());
+			}
+			let hash_type = if payload[0] == FULL_DATA_FORMAT {
+				ScriptHashType::Data
+			} else {
+				ScriptHashType::Type
+			};
+			full_script(&payload[1..33], hash_type, &payload[33..])
+		},
+		(Some(&FULL_FORMAT), Variant::Bech32m) => {
+			if payload.len() < 34 {
+				return Err(invalid());
+			}
+			let hash_type = match payload[33] {
+				0 => ScriptHashType::Data,
+				1 => ScriptHashType::Type,
+				2 => ScriptHashType::Data1,
+				_ => return Err(invalid())
+			};
+			full_script(&payload[1..33], hash_type, &payload[34..])
+		},
+		_ => Err(invalid())
+	}
+}
+
+// format lock_script into full ckb address of the connected network
+pub fn format_address(script: &Script) -> Result<String, String> {
+	let mut payload = vec![FULL_FORMAT];
+	payload.extend(script.code_hash().as_slice());
+	payload.push(script.hash_type().into());
+	payload.extend(script.args().raw_data().to_vec());
+	bech32::encode(network()?.prefix(), payload.to_base32(), Variant::Bech32m).map_err(|err| err.to_string())
+}
+
+// accept either blake160 hex or ckb address of sighash lock_script as the target of transfer
+pub fn parse_recipient(to: &str) -> Result<[u8; 20], String> {
+	let hex = to.trim_start_matches("0x");
+	if hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+		return blake160_to_byte20(hex).map_err(|err| err.to_string());
+	}
+	let script = parse_address(to)?;
+	let sighash = sighash_script(&vec![0u8; 20]);
+	if script.code_hash() != sighash.code_hash() || script.hash_type() != sighash.hash_type() {
+		return Err(format!("address {} isn't a secp256k1_blake160_sighash_all address", to));
+	}
+	let args = script.args().raw_data();
+	if args.len() != 20 {
+		return Err(format!("invalid args length {} of sighash address {}", args.len(), to));
+	}
+	let mut pkhash = [0u8; 20];
+	pkhash.copy_from_slice(&args);
+	Ok(pkhash)
+}
+
+// receive address of current user
+pub fn my_address() -> Result<String, String> {
+	format_address(&sighash_script(&active_pubhash().to_vec()))
+}
\ No newline at end of file
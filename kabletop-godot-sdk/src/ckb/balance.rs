@@ -0,0 +1,147 @@
+use super::{
+	rpc, tracker::uncommitted_transactions
+};
+use crate::account::{
+	active_pubhash, db_dir
+};
+use kabletop_ckb_sdk::ckb::{
+	wallet::keystore, transaction::helper::{
+		sighash_script, wallet_script, nft_script, payment_script
+	}
+};
+use ckb_types::prelude::*;
+use std::{
+	sync::Mutex, collections::HashMap
+};
+
+// the fee of opening channel is unknown until both players complete the transaction, so a generous one is reserved
+const CHANNEL_FEE_RESERVE: u64 = 1_000_000;
+
+// where user's ckb are, all amounts are in shannons
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Balance {
+	// capacity of all live cells locked by user
+	pub total:            u64,
+	// plain cells without type script and data
+	pub free:             u64,
+	// free capacity which isn't being consumed by a transaction in tx-pool
+	pub spendable:        u64,
+	pub nft_occupied:     u64,
+	// cells with other type scripts or data
+	pub other_occupied:   u64,
+	// user's wallet cell which is locked by composer
+	pub wallet:           u64,
+	// user's staking in kabletop channels which are still alive
+	pub staked:           u64,
+	// capacity which tracked transactions in tx-pool will create for or consume from user
+	pub pending_incoming: u64,
+	pub pending_outgoing: u64
+}
+
+lazy_static! {
+	// guards the open channel list of every account against concurrent updates
+	static ref OPEN_CHANNELS: Mutex<()> = Mutex::new(());
+}
+
+// channels of active account which are opened but not closed yet, keyed by hex script_args with staking in shannons,
+// which is kept in a sub directory so that scanning channel caches in db_dir never meets it
+fn open_channels_path() -> String {
+	format!("{}/staking/open_channels.json", db_dir())
+}
+
+fn load_open_channels(path: &str) -> Result<HashMap<String, u64>, String> {
+	match std::fs::read_to_string(path) {
+		Ok(content) => serde_json::from_str(content.as_str()).map_err(|err| format!("{} => {}", err, path)),
+		Err(_)      => Ok(HashMap::new())
+	}
+}
+
+// the channel itself is already settled on chain, so failing to record it only skews the balance
+fn update_open_channels<F: FnOnce(&mut HashMap<String, u64>)>(f: F) {
+	let _guard = OPEN_CHANNELS.lock().unwrap();
+	let path = open_channels_path();
+	let result = load_open_channels(&path).and_then(|mut channels| {
+		f(&mut channels);
+		let content = serde_json::to_string_pretty(&channels).unwrap_or_default();
+		std::fs::create_dir_all(format!("{}/staking", db_dir()))
+			.and_then(|_| std::fs::write(&path, content))
+			.map_err(|err| format!("{} => {}", err, path))
+	});
+	if let Err(err) = result {
+		println!("persist open channels error: {}", err);
+	}
+}
+
+// record a channel once its open transaction is committed, so that its staking is counted without asking the chain
+pub fn record_open_channel(script_args: &[u8], staking_ckb: u64) {
+	update_open_channels(|channels| {
+		channels.insert(hex::encode(script_args), staking_ckb);
+	});
+}
+
+// forget a channel once its close transaction is committed
+pub fn record_closed_channel(script_args: &[u8]) {
+	update_open_channels(|channels| {
+		channels.remove(&hex::encode(script_args));
+	});
+}
+
+// staking of the channels which are still open
+fn staked_capacity() -> Result<u64, String> {
+	let _guard = OPEN_CHANNELS.lock().unwrap();
+	Ok(load_open_channels(&open_channels_path())?.values().sum())
+}
+
+pub fn balance() -> Result<Balance, String> {
+	let user_lock = sighash_script(&active_pubhash().to_vec());
+	let wallet = wallet_script(keystore::COMPOSER_PUBHASH.to_vec());
+	let nft_type = nft_script(wallet.calc_script_hash().raw_data().to_vec());
+	let mut balance = Balance::default();
+	for (_, output, data) in rpc::get_live_cells(&user_lock, None, usize::MAX)? {
+		let capacity: u64 = output.capacity().unpack();
+		balance.total += capacity;
+		match output.type_().to_opt() {
+			Some(script) if script == nft_type => balance.nft_occupied += capacity,
+			None if data.is_empty()            => balance.free += capacity,
+			_                                  => balance.other_occupied += capacity
+		}
+	}
+	balance.wallet = rpc::get_live_cells(&wallet, Some(&payment_script(active_pubhash().to_vec())), 1)?
+		.into_iter()
+		.map(|(_, output, _)| Unpack::<u64>::unpack(&output.capacity()))
+		.sum();
+	balance.staked = staked_capacity()?;
+	let mut pending_free = 0;
+	for hash in uncommitted_transactions() {
+		let tx = rpc::get_transaction(&hash)?;
+		for input in tx.raw().inputs() {
+			let (output, data) = rpc::get_cell(&input.previous_output())?;
+			if output.lock() == user_lock {
+				let capacity: u64 = output.capacity().unpack();
+				balance.pending_outgoing += capacity;
+				if output.type_().is_none() && data.is_empty() {
+					pending_free += capacity;
+				}
+			}
+		}
+		balance.pending_incoming += tx
+			.raw()
+			.outputs()
+			.into_iter()
+			.filter(|output| output.lock() == user_lock)
+			.map(|output| Unpack::<u64>::unpack(&output.capacity()))
+			.sum::<u64>();
+	}
+	balance.spendable = balance.free.saturating_sub(pending_free);
+	Ok(balance)
+}
+
+// make sure the staking of a new channel together with its fee can be paid from spendable capacity
+pub fn check_channel_balance(staking_ckb: u64) -> Result<(), String> {
+	let spendable = balance()?.spendable;
+	let required = staking_ckb + CHANNEL_FEE_RESERVE;
+	if spendable < required {
+		return Err(format!("opening channel needs {} shannons spendable, but only {} is available", required, spendable));
+	}
+	Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,151 @@
+use super::{
+	fee::{
+		transaction_fee, DEFAULT_FEE_RATE
+	}, wallet::push_transaction, nft::{
+		nft_type_script, nft_output, owner_cells, build_user_transaction, Cell
+	}
+};
+use crate::account::{
+	active_pubhash, active_signer
+};
+use kabletop_ckb_sdk::ckb::transaction::helper::sighash_script;
+use ckb_types::{
+	H256, core::TransactionView, prelude::*, packed::Script
+};
+use std::thread;
+
+// inputs merged by one transaction are limited to keep it far below the size limit of block
+const MAX_NFT_CELLS: usize = 50;
+const MAX_CKB_CELLS: usize = 50;
+
+// the rest of fragments are left to the next call if consolidation still needs more transactions
+const MAX_TRANSACTIONS: usize = 5;
+
+// how many cells of user will be left after consolidation
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConsolidationPreview {
+	pub nft_cells_before: usize,
+	pub nft_cells_after:  usize,
+	pub ckb_cells_before: usize,
+	pub ckb_cells_after:  usize,
+	pub transactions:     usize,
+	// only the fee of the first transaction is exact, each of the following ones is assumed to pay the same
+	pub estimated_fee:    u64
+}
+
+// lock_script of user and type_script of nft along with nft cells and plain ckb cells of user
+fn user_cells() -> Result<(Script, Script, Vec<Cell>, Vec<Cell>), String> {
+	let (nft_cells, ckb_cells) = owner_cells(&active_pubhash())?;
+	Ok((sighash_script(&active_pubhash().to_vec()), nft_type_script(), nft_cells, ckb_cells))
+}
+
+// nfts of the leading nft cells are merged into one nft cell of user, and plain cells are put into the same
+// transaction to join its change output, which is a plain self-transfer if there are no nft cells to merge
+fn build_consolidation(nft_cells: &[Cell], ckb_cells: &[Cell], fee_rate: u64) -> Result<Option<TransactionView>, String> {
+	if nft_cells.len() <= 1 && ckb_cells.len() <= 1 {
+		return Ok(None);
+	}
+	if nft_cells.len() <= 1 {
+		let inputs = ckb_cells.iter().take(MAX_CKB_CELLS).cloned().collect::<Vec<_>>();
+		return Ok(Some(build_user_transaction(inputs, vec![], ckb_cells, fee_rate)?));
+	}
+	let nft_inputs = nft_cells.iter().take(MAX_NFT_CELLS).cloned().collect::<Vec<_>>();
+	let nfts = nft_inputs
+		.iter()
+		.map(|(_, _, data)| data.chunks_exact(20).map(|hash| {
+			let mut nft = [0u8; 20];
+			nft.copy_from_slice(hash);
+			nft
+		}).collect::<Vec<_>>())
+		.flatten()
+		.collect::<Vec<_>>();
+	let inputs = nft_inputs
+		.into_iter()
+		.chain(ckb_cells.iter().take(MAX_CKB_CELLS).cloned())
+		.collect::<Vec<_>>();
+	let tx = build_user_transaction(inputs, vec![nft_output(&active_pubhash(), &nfts)?], ckb_cells, fee_rate)?;
+	Ok(Some(tx))
+}
+
+// count user's nft and plain cells which are left after the transaction is committed
+fn cells_after(tx: &TransactionView, user_lock: &Script, nft_type: &Script, nft_cells: &[Cell], ckb_cells: &[Cell]) -> (usize, usize) {
+	let consumed = tx.inputs().into_iter().map(|input| input.previous_output()).collect::<Vec<_>>();
+	let left = |cells: &[Cell]| cells.iter().filter(|(out_point, _, _)| !consumed.contains(out_point)).count();
+	let mut nft_count = left(nft_cells);
+	let mut ckb_count = left(ckb_cells);
+	for (output, data) in tx.outputs_with_data_iter() {
+		if &output.lock() != user_lock {
+			continue
+		}
+		match output.type_().to_opt() {
+			Some(script) if &script == nft_type => nft_count += 1,
+			None if data.is_empty()             => ckb_count += 1,
+			_                                   => ()
+		}
+	}
+	(nft_count, ckb_count)
+}
+
+// preview consolidation by building its first transaction without submitting it
+pub fn preview_consolidation(fee_rate: Option<u64>) -> Result<ConsolidationPreview, String> {
+	let (user_lock, nft_type, nft_cells, ckb_cells) = user_cells()?;
+	let mut preview = ConsolidationPreview {
+		nft_cells_before: nft_cells.len(),
+		nft_cells_after:  nft_cells.len(),
+		ckb_cells_before: ckb_cells.len(),
+		ckb_cells_after:  ckb_cells.len(),
+		transactions:     0,
+		estimated_fee:    0
+	};
+	let tx = match build_consolidation(&nft_cells, &ckb_cells, fee_rate.unwrap_or(DEFAULT_FEE_RATE))? {
+		Some(tx) => tx,
+		None     => return Ok(preview)
+	};
+	let fee = transaction_fee(&tx)?;
+	let (nft_count, ckb_count) = cells_after(&tx, &user_lock, &nft_type, &nft_cells, &ckb_cells);
+	// every following transaction merges at most MAX_*_CELLS fragments into the one left by previous transaction
+	let rounds = |count: usize, max: usize| (count.saturating_sub(1) + max - 2) / (max - 1);
+	let more = std::cmp::max(rounds(nft_count, MAX_NFT_CELLS), rounds(ckb_count, MAX_CKB_CELLS));
+	preview.transactions = std::cmp::min(1 + more, MAX_TRANSACTIONS);
+	preview.estimated_fee = fee * preview.transactions as u64;
+	if more == 0 {
+		preview.nft_cells_after = nft_count;
+		preview.ckb_cells_after = ckb_count;
+	} else {
+		preview.nft_cells_after = std::cmp::min(nft_count, 1);
+		preview.ckb_cells_after = std::cmp::min(ckb_count, 1);
+	}
+	Ok(preview)
+}
+
+// merge user's nft cells and plain cells in one or a few transactions, each of them is committed before the next
+pub fn consolidate_cells<F>(fee_rate: Option<u64>, f: F)
+where
+	F: Fn(Result<Vec<H256>, String>) + Send + 'static
+{
+	thread::spawn(move || {
+		let mut hashes = vec![];
+		let mut fragments = usize::MAX;
+		while hashes.len() < MAX_TRANSACTIONS {
+			let (_, _, nft_cells, ckb_cells) = match user_cells() {
+				Ok(cells) => cells,
+				Err(err)  => return f(Err(err))
+			};
+			// stop once a transaction can't reduce cells any more
+			if nft_cells.len() + ckb_cells.len() >= fragments {
+				break
+			}
+			fragments = nft_cells.len() + ckb_cells.len();
+			let tx = match build_consolidation(&nft_cells, &ckb_cells, fee_rate.unwrap_or(DEFAULT_FEE_RATE)) {
+				Ok(Some(tx)) => tx,
+				Ok(None)     => break,
+				Err(err)     => return f(Err(err))
+			};
+			match active_signer().sign_tx(tx).and_then(push_transaction) {
+				Ok(hash) => hashes.push(hash),
+				Err(err) => return f(Err(err))
+			}
+		}
+		f(Ok(hashes))
+	});
+}
\ No newline at end of file
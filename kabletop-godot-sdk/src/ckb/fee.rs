@@ -0,0 +1,141 @@
+use super::rpc;
+use crate::account::active_pubhash;
+use kabletop_ckb_sdk::ckb::transaction::helper::sighash_script;
+use ckb_types::{
+	bytes::Bytes, core::{
+		TransactionView, Capacity
+	}, packed::{
+		WitnessArgs, BytesOpt
+	}, prelude::*
+};
+
+// shannons paid for every 1000 bytes of transaction, which is the minimal fee rate accepted by ckb node
+pub const DEFAULT_FEE_RATE: u64 = 1000;
+
+// fee paid by transaction is the total capacity of inputs minus the total capacity of outputs
+pub fn transaction_fee(tx: &TransactionView) -> Result<u64, String> {
+	let mut inputs_capacity = 0u64;
+	for input in tx.inputs() {
+		let (output, _) = rpc::get_cell(&input.previous_output())?;
+		inputs_capacity += Unpack::<u64>::unpack(&output.capacity());
+	}
+	let outputs_capacity = tx
+		.outputs_capacity()
+		.map_err(|err| err.to_string())?
+		.as_u64();
+	if inputs_capacity < outputs_capacity {
+		return Err(format!("inputs capacity {} is less than outputs capacity {}", inputs_capacity, outputs_capacity));
+	}
+	Ok(inputs_capacity - outputs_capacity)
+}
+
+// the fee which transaction should pay under specified fee rate
+pub fn required_fee(tx: &TransactionView, fee_rate: u64) -> u64 {
+	let size = tx.data().serialized_size_in_block() as u64;
+	(size * fee_rate + 999) / 1000
+}
+
+// every lock group of inputs is unlocked by the lock field of its first witness, the missing ones are filled with
+// placeholders as long as a secp256k1 signature so that the size is the one of signed transaction
+fn with_placeholder_witnesses(tx: &TransactionView) -> Result<TransactionView, String> {
+	let mut witnesses = tx.witnesses().into_iter().map(|witness| witness.raw_data()).collect::<Vec<_>>();
+	while witnesses.len() < tx.inputs().len() {
+		witnesses.push(Bytes::new());
+	}
+	let mut groups = vec![];
+	for (i, input) in tx.inputs().into_iter().enumerate() {
+		let (output, _) = rpc::get_cell(&input.previous_output())?;
+		let lock_hash = output.calc_lock_hash();
+		if groups.contains(&lock_hash) {
+			continue
+		}
+		groups.push(lock_hash);
+		if witnesses[i].is_empty() {
+			witnesses[i] = WitnessArgs::new_builder()
+				.lock(BytesOpt::new_builder().set(Some(Bytes::from(vec![0u8; 65]).pack())).build())
+				.build()
+				.as_bytes();
+		}
+	}
+	Ok(tx
+		.as_advanced_builder()
+		.set_witnesses(witnesses.into_iter().map(|witness| witness.pack()).collect())
+		.build())
+}
+
+// the fee which transaction should pay under specified fee rate once all of its lock groups are signed
+pub fn signed_required_fee(tx: &TransactionView, fee_rate: u64) -> Result<u64, String> {
+	Ok(required_fee(&with_placeholder_witnesses(tx)?, fee_rate))
+}
+
+// changing outputs invalidates signatures, which is fine for user who signs afterwards but not for anyone else
+fn check_foreign_signatures(tx: &TransactionView) -> Result<(), String> {
+	let user_lock = sighash_script(&active_pubhash().to_vec());
+	let sighash_code = user_lock.code_hash();
+	let witnesses = tx.witnesses();
+	for (i, input) in tx.inputs().into_iter().enumerate() {
+		let (output, _) = rpc::get_cell(&input.previous_output())?;
+		let lock = output.lock();
+		if lock == user_lock || lock.code_hash() != sighash_code {
+			continue
+		}
+		if witnesses.get(i).map(|witness| !witness.raw_data().is_empty()).unwrap_or(false) {
+			return Err(String::from("transaction contains inputs signed by others, its fee can't be adjusted any more"));
+		}
+	}
+	Ok(())
+}
+
+// the change output is the last output locked by user's sighash lock_script without type_script
+pub fn change_output_index(tx: &TransactionView) -> Result<usize, String> {
+	let user_lock = sighash_script(&active_pubhash().to_vec());
+	tx
+		.outputs()
+		.into_iter()
+		.enumerate()
+		.filter(|(_, output)| output.lock() == user_lock && output.type_().is_none())
+		.map(|(i, _)| i)
+		.last()
+		.ok_or(String::from("no change output of user to adjust fee"))
+}
+
+// adjust the change output of user to make transaction pay fee in specified fee rate, the transaction is left
+// unsigned for user, and signatures of other signers must not be there yet
+pub fn apply_fee_rate(tx: TransactionView, fee_rate: u64) -> Result<TransactionView, String> {
+	check_foreign_signatures(&tx)?;
+	let fee = transaction_fee(&tx)?;
+	let required = signed_required_fee(&tx, fee_rate)?;
+	if fee == required {
+		return Ok(tx);
+	}
+	let change_index = change_output_index(&tx)?;
+	let change = tx.outputs().get(change_index).unwrap();
+	let change_data = tx.outputs_data().get(change_index).unwrap();
+	let capacity: u64 = change.capacity().unpack();
+	let capacity = (capacity + fee)
+		.checked_sub(required)
+		.ok_or(String::from("change capacity is insufficient to pay fee"))?;
+	let occupied = change
+		.occupied_capacity(Capacity::bytes(change_data.raw_data().len()).map_err(|err| err.to_string())?)
+		.map_err(|err| err.to_string())?
+		.as_u64();
+	if capacity < occupied {
+		return Err(format!("change capacity {} is less than occupied capacity {} after paying fee", capacity, occupied));
+	}
+	let outputs = tx
+		.outputs()
+		.into_iter()
+		.enumerate()
+		.map(|(i, output)| {
+			if i == change_index {
+				output.as_builder().capacity(capacity.pack()).build()
+			} else {
+				output
+			}
+		})
+		.collect::<Vec<_>>();
+	Ok(tx
+		.as_advanced_builder()
+		.set_outputs(outputs)
+		.build())
+}
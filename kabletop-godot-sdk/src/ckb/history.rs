@@ -0,0 +1,185 @@
+use super::{
+	rpc, pending::{
+		summarize_transaction, TransactionSummary
+	}
+};
+use crate::account::active_pubhash;
+use kabletop_ckb_sdk::ckb::{
+	wallet::keystore, transaction::helper::{
+		sighash_script, wallet_script, nft_script, kabletop_script
+	}
+};
+use ckb_types::{
+	H256, prelude::*, packed::Script
+};
+
+// what a historical transaction did from the view of user
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TransactionKind {
+	Purchase,
+	Reveal,
+	TransferIn,
+	TransferOut,
+	Discard,
+	ChannelOpen,
+	ChannelClose,
+	Challenge,
+	Other
+}
+
+impl TransactionKind {
+	pub fn name(&self) -> &'static str {
+		match self {
+			TransactionKind::Purchase     => "purchase",
+			TransactionKind::Reveal       => "reveal",
+			TransactionKind::TransferIn   => "transfer_in",
+			TransactionKind::TransferOut  => "transfer_out",
+			TransactionKind::Discard      => "discard",
+			TransactionKind::ChannelOpen  => "channel_open",
+			TransactionKind::ChannelClose => "channel_close",
+			TransactionKind::Challenge    => "challenge",
+			TransactionKind::Other        => "other"
+		}
+	}
+}
+
+#[derive(Clone)]
+pub struct TransactionRecord {
+	pub block_number: u64,
+	pub kind:         TransactionKind,
+	pub summary:      TransactionSummary
+}
+
+fn classify_transaction(summary: &TransactionSummary) -> TransactionKind {
+	let kabletop_code: [u8; 32] = kabletop_script(vec![]).code_hash().unpack();
+	let wallet_code: [u8; 32] = wallet_script(keystore::COMPOSER_PUBHASH.to_vec()).code_hash().unpack();
+	let kabletop_in = summary.inputs.iter().any(|cell| cell.lock_code == kabletop_code);
+	let kabletop_out = summary.outputs.iter().any(|cell| cell.lock_code == kabletop_code);
+	let wallet_involved = summary.inputs.iter().chain(summary.outputs.iter()).any(|cell| cell.lock_code == wallet_code);
+	let user_paid = summary.inputs.iter().any(|cell| cell.is_user);
+	match (kabletop_in, kabletop_out) {
+		(true, true)   => return TransactionKind::Challenge,
+		(true, false)  => return TransactionKind::ChannelClose,
+		(false, true)  => return TransactionKind::ChannelOpen,
+		(false, false) => ()
+	}
+	if wallet_involved {
+		if summary.nfts_received.is_empty() {
+			return TransactionKind::Purchase;
+		}
+		return TransactionKind::Reveal;
+	}
+	if !summary.nfts_sent.is_empty() {
+		let nfts_to_others = summary.outputs.iter().any(|cell| !cell.is_user && !cell.nfts.is_empty());
+		if nfts_to_others {
+			return TransactionKind::TransferOut;
+		}
+		return TransactionKind::Discard;
+	}
+	if !summary.nfts_received.is_empty() || !user_paid {
+		return TransactionKind::TransferIn;
+	}
+	TransactionKind::Other
+}
+
+fn into_cursor(position: (u64, u32)) -> String {
+	format!("{}-{}", position.0, position.1)
+}
+
+fn from_cursor(cursor: &str) -> Result<(u64, u32), String> {
+	let mut parts = cursor.split('-');
+	match (parts.next(), parts.next(), parts.next()) {
+		(Some(block), Some(index), None) => {
+			let block = block.parse::<u64>().map_err(|err| err.to_string())?;
+			let index = index.parse::<u32>().map_err(|err| err.to_string())?;
+			Ok((block, index))
+		},
+		_ => Err(format!("invalid history cursor {}", cursor))
+	}
+}
+
+// search at most `count` transactions older than `before` from indexer, newest first
+fn search_transactions(
+	lock_script: &Script, type_script: Option<&Script>, before: Option<(u64, u32)>, count: usize
+) -> Result<Vec<(H256, (u64, u32))>, String> {
+	let block_range = before.map(|(block, _)| (0, block + 1));
+	let mut hashes: Vec<(H256, (u64, u32))> = vec![];
+	let mut after = None;
+	'search: loop {
+		let page = rpc::get_transactions(lock_script, type_script, block_range, 64, after)?;
+		if page.objects.is_empty() {
+			break
+		}
+		for object in page.objects {
+			let position = (object.block_number.value(), object.tx_index.value());
+			if before.map_or(false, |before| position >= before) {
+				continue
+			}
+			if hashes.iter().any(|(hash, _)| hash == &object.tx_hash) {
+				continue
+			}
+			if hashes.len() == count {
+				break 'search
+			}
+			hashes.push((object.tx_hash, position));
+		}
+		after = Some(page.last_cursor);
+	}
+	Ok(hashes)
+}
+
+// list transactions touching user's cells or nft cells from the newest to the oldest, the cursor returned
+// can be passed in to continue listing the older ones, and None cursor means no more transactions
+pub fn transaction_history(limit: usize, cursor: Option<String>) -> Result<(Vec<TransactionRecord>, Option<String>), String> {
+	if limit == 0 {
+		return Err(String::from("limit of transaction history must be greater than 0"));
+	}
+	let before = match cursor {
+		Some(cursor) => Some(from_cursor(cursor.as_str())?),
+		None         => None
+	};
+	let user_lock = sighash_script(&active_pubhash().to_vec());
+	let nft_type = {
+		let wallet = wallet_script(keystore::COMPOSER_PUBHASH.to_vec());
+		nft_script(wallet.calc_script_hash().raw_data().to_vec())
+	};
+	// one more transaction than limit tells whether there are older ones
+	let mut hashes = search_transactions(&user_lock, None, before, limit + 1)?;
+	for (hash, position) in search_transactions(&user_lock, Some(&nft_type), before, limit + 1)? {
+		if !hashes.iter().any(|(value, _)| value == &hash) {
+			hashes.push((hash, position));
+		}
+	}
+	hashes.sort_by(|a, b| b.1.cmp(&a.1));
+	let has_more = hashes.len() > limit;
+	hashes.truncate(limit);
+	let mut records = vec![];
+	for (hash, (block_number, _)) in &hashes {
+		let tx = rpc::get_transaction(hash)?.into_view();
+		if tx.is_cellbase() {
+			continue
+		}
+		let summary = summarize_transaction(&tx)?;
+		records.push(TransactionRecord {
+			block_number: *block_number,
+			kind:         classify_transaction(&summary),
+			summary
+		});
+	}
+	let cursor = if has_more {
+		hashes.last().map(|(_, position)| into_cursor(*position))
+	} else {
+		None
+	};
+	Ok((records, cursor))
+}
+
+// the transaction which finally consumed the kabletop cell of a closed channel along with the timestamp of its block,
+// challenges consume the cell too but always before the close
+pub fn find_close_transaction(script_args: Vec<u8>) -> Result<Option<(H256, u64)>, String> {
+	let page = rpc::get_transactions(&kabletop_script(script_args), None, None, 64, None)?;
+	match page.objects.into_iter().find(|object| object.io_type == "input") {
+		Some(object) => Ok(Some((object.tx_hash, rpc::get_block_timestamp(object.block_number.value())?))),
+		None         => Ok(None)
+	}
+}
\ No newline at end of file
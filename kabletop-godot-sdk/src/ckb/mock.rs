@@ -0,0 +1,367 @@
+use super::{
+	mock_node, nft::nft_output
+};
+use kabletop_ckb_sdk::{
+	config::VARS, ckb::transaction::helper::sighash_script
+};
+use ckb_types::{
+	H256, prelude::*, core::{
+		TransactionBuilder, HeaderBuilder, HeaderView
+	}, packed::{
+		Transaction, CellInput, CellOutput, OutPoint, Script
+	}
+};
+use std::{
+	sync::Mutex, collections::{
+		HashMap, VecDeque
+	}
+};
+
+// failures which are injected into mock chain, each of them takes effect only once
+#[derive(Clone, Debug)]
+pub enum MockFailure {
+	// the next submitted transaction is refused by send_transaction
+	RejectTransaction(String),
+	// the next submitted transaction is accepted but never committed, as if it's dropped from tx-pool
+	DropTransaction,
+	// the next rpc request returns an error
+	RpcError(String)
+}
+
+struct MockCell {
+	output:       CellOutput,
+	data:         Vec<u8>,
+	block_number: u64,
+	tx_index:     u32,
+	live:         bool
+}
+
+struct MockTx {
+	tx:           Transaction,
+	block_number: Option<u64>,
+	tx_index:     u32,
+	dropped:      bool
+}
+
+struct MockChain {
+	enabled:      bool,
+	served:       bool,
+	auto_mine:    bool,
+	tip:          u64,
+	// how many cells are issued, which keeps funding transactions of the same output apart
+	issued:       u64,
+	cells:        HashMap<OutPoint, MockCell>,
+	transactions: HashMap<H256, MockTx>,
+	pool:         Vec<H256>,
+	failures:     VecDeque<MockFailure>
+}
+
+lazy_static! {
+	static ref CHAIN: Mutex<MockChain> = Mutex::new(MockChain {
+		enabled:      std::env::var("KABLETOP_MOCK_CHAIN").map_or(false, |value| value == "1" || value == "true"),
+		served:       false,
+		auto_mine:    false,
+		tip:          0,
+		issued:       0,
+		cells:        HashMap::new(),
+		transactions: HashMap::new(),
+		pool:         vec![],
+		failures:     VecDeque::new()
+	});
+}
+
+// switch rpc requests to the in-process mock chain, which is shared by every peer in the same process
+//
+// the mock chain is only reached as json-rpc served on the configured ckb_uri and ckb_indexer_uri, the same as
+// kabletop-ckb-sdk reaches a node, and those local addresses stay bound (and refuse requests while disabled) once served
+pub fn set_mock_chain(enabled: bool) -> Result<(), String> {
+	CHAIN.lock().unwrap().enabled = enabled;
+	if enabled {
+		serve_configured_uris()?;
+	}
+	Ok(())
+}
+
+pub fn is_mock_chain() -> bool {
+	CHAIN.lock().unwrap().enabled
+}
+
+// mock chain enabled by KABLETOP_MOCK_CHAIN is served right before the first request is sent
+pub(super) fn serve_if_enabled() {
+	let (enabled, served) = {
+		let chain = CHAIN.lock().unwrap();
+		(chain.enabled, chain.served)
+	};
+	if enabled && !served {
+		if let Err(err) = serve_configured_uris() {
+			println!("serve mock chain error: {}", err);
+		}
+	}
+}
+
+fn serve_configured_uris() -> Result<(), String> {
+	let mut chain = CHAIN.lock().unwrap();
+	if chain.served {
+		return Ok(());
+	}
+	let mut addresses = vec![];
+	for uri in vec![&VARS.common.ckb_uri, &VARS.common.ckb_indexer_uri] {
+		let address = mock_node::local_address(uri)?;
+		if !addresses.contains(&address) {
+			addresses.push(address);
+		}
+	}
+	for address in addresses {
+		mock_node::serve(&address)?;
+	}
+	chain.served = true;
+	Ok(())
+}
+
+// commit every submitted transaction into a new block at once
+pub fn set_mock_auto_mine(auto_mine: bool) {
+	CHAIN.lock().unwrap().auto_mine = auto_mine;
+}
+
+pub fn inject_mock_failure(failure: MockFailure) {
+	CHAIN.lock().unwrap().failures.push_back(failure);
+}
+
+// clear all cells, transactions and failures, and restart from block 0
+pub fn reset_mock_chain() {
+	let mut chain = CHAIN.lock().unwrap();
+	chain.tip = 0;
+	chain.cells.clear();
+	chain.transactions.clear();
+	chain.pool.clear();
+	chain.failures.clear();
+}
+
+impl MockChain {
+	fn take_rpc_error(&mut self) -> Result<(), String> {
+		if let Some(MockFailure::RpcError(_)) = self.failures.front() {
+			if let Some(MockFailure::RpcError(error)) = self.failures.pop_front() {
+				return Err(error);
+			}
+		}
+		Ok(())
+	}
+
+	fn commit(&mut self, hash: &H256, tx_index: u32) {
+		let block_number = self.tip;
+		let tx = {
+			let value = self.transactions.get_mut(hash).unwrap();
+			value.block_number = Some(block_number);
+			value.tx_index = tx_index;
+			value.tx.clone()
+		};
+		for input in tx.raw().inputs() {
+			if let Some(cell) = self.cells.get_mut(&input.previous_output()) {
+				cell.live = false;
+			}
+		}
+		for (i, (output, data)) in tx.raw().outputs().into_iter().zip(tx.raw().outputs_data().into_iter()).enumerate() {
+			let out_point = OutPoint::new_builder()
+				.tx_hash(hash.pack())
+				.index((i as u32).pack())
+				.build();
+			self.cells.insert(out_point, MockCell {
+				output,
+				data: data.raw_data().to_vec(),
+				block_number,
+				tx_index,
+				live: true
+			});
+		}
+	}
+
+	fn advance(&mut self, count: u64) {
+		for _ in 0..count {
+			self.tip += 1;
+			let pool = std::mem::take(&mut self.pool);
+			for (i, hash) in pool.iter().enumerate() {
+				self.commit(hash, i as u32 + 1);
+			}
+		}
+	}
+}
+
+// build new blocks, the first of which commits all transactions in tx-pool
+pub fn advance_mock_blocks(count: u64) {
+	CHAIN.lock().unwrap().advance(count);
+}
+
+// headers of mock chain only carry the block number, which is enough to tell blocks apart
+pub(super) fn mock_header(block_number: u64) -> HeaderView {
+	HeaderBuilder::default()
+		.number(block_number.pack())
+		.build()
+}
+
+pub(super) fn mock_header_by_hash(hash: &H256) -> Result<Option<HeaderView>, String> {
+	CHAIN.lock().unwrap().take_rpc_error()?;
+	Ok((0..=mock_tip()).map(mock_header).find(|header| &Unpack::<H256>::unpack(&header.hash()) == hash))
+}
+
+// create a live cell out of thin air in the tip block, which is used to fund test accounts, the funding transaction
+// spends a cellbase-like input numbered by issued count so that no two of them share the same hash
+pub fn issue_mock_cell(output: CellOutput, data: Vec<u8>) -> OutPoint {
+	let mut chain = CHAIN.lock().unwrap();
+	chain.issued += 1;
+	let tx = TransactionBuilder::default()
+		.input(CellInput::new_cellbase_input(chain.issued))
+		.output(output)
+		.output_data(data.pack())
+		.build();
+	let hash: H256 = tx.hash().unpack();
+	chain.transactions.insert(hash.clone(), MockTx {
+		tx:           tx.data(),
+		block_number: None,
+		tx_index:     0,
+		dropped:      false
+	});
+	chain.commit(&hash, 0);
+	OutPoint::new_builder()
+		.tx_hash(hash.pack())
+		.index(0u32.pack())
+		.build()
+}
+
+// fund the owner with a plain sighash cell
+pub fn issue_mock_capacity(owner: &[u8; 20], capacity: u64) -> OutPoint {
+	let output = CellOutput::new_builder()
+		.lock(sighash_script(&owner.to_vec()))
+		.capacity(capacity.pack())
+		.build();
+	issue_mock_cell(output, vec![])
+}
+
+// give the owner one nft cell which holds nfts
+pub fn issue_mock_nfts(owner: &[u8; 20], nfts: &[[u8; 20]]) -> Result<OutPoint, String> {
+	let (output, data) = nft_output(owner, nfts)?;
+	Ok(issue_mock_cell(output, data.to_vec()))
+}
+
+pub fn mock_send_transaction(tx: Transaction) -> Result<H256, String> {
+	let mut chain = CHAIN.lock().unwrap();
+	chain.take_rpc_error()?;
+	let hash: H256 = tx.calc_tx_hash().unpack();
+	let mut dropped = false;
+	match chain.failures.front() {
+		Some(MockFailure::RejectTransaction(_)) => {
+			if let Some(MockFailure::RejectTransaction(reason)) = chain.failures.pop_front() {
+				return Err(format!("send_transaction -> {}", reason));
+			}
+		},
+		Some(MockFailure::DropTransaction) => {
+			chain.failures.pop_front();
+			dropped = true;
+		},
+		_ => ()
+	}
+	for input in tx.raw().inputs() {
+		let out_point = input.previous_output();
+		let live = chain.cells.get(&out_point).map_or(false, |cell| cell.live);
+		let spent_in_pool = chain.pool.iter().any(|pending| {
+			chain.transactions[pending].tx.raw().inputs().into_iter().any(|value| value.previous_output() == out_point)
+		});
+		if !live || spent_in_pool {
+			return Err(format!("send_transaction -> input {} is dead or unknown", out_point));
+		}
+	}
+	if chain.transactions.contains_key(&hash) {
+		return Err(format!("send_transaction -> duplicated transaction {}", hash));
+	}
+	chain.transactions.insert(hash.clone(), MockTx {
+		tx,
+		block_number: None,
+		tx_index:     0,
+		dropped
+	});
+	if !dropped {
+		chain.pool.push(hash.clone());
+		if chain.auto_mine {
+			chain.advance(1);
+		}
+	}
+	Ok(hash)
+}
+
+pub fn mock_tip_block_number() -> Result<u64, String> {
+	let mut chain = CHAIN.lock().unwrap();
+	chain.take_rpc_error()?;
+	Ok(chain.tip)
+}
+
+// the tip which other methods refer to, it never takes an injected failure that is meant for the method itself
+pub(super) fn mock_tip() -> u64 {
+	CHAIN.lock().unwrap().tip
+}
+
+// the transaction and the block number which commits it, dropped transactions are unknown
+pub fn mock_get_transaction(hash: &H256) -> Result<Option<(Transaction, Option<u64>)>, String> {
+	let mut chain = CHAIN.lock().unwrap();
+	chain.take_rpc_error()?;
+	Ok(chain.transactions
+		.get(hash)
+		.filter(|value| !value.dropped)
+		.map(|value| (value.tx.clone(), value.block_number)))
+}
+
+// the cell which out_point points to, along with whether it's still live
+pub(super) fn mock_get_cell(out_point: &OutPoint) -> Result<Option<(CellOutput, Vec<u8>, bool)>, String> {
+	let mut chain = CHAIN.lock().unwrap();
+	chain.take_rpc_error()?;
+	Ok(chain.cells.get(out_point).map(|cell| (cell.output.clone(), cell.data.clone(), cell.live)))
+}
+
+fn matches_scripts(output: &CellOutput, lock_script: Option<&Script>, type_script: Option<&Script>) -> bool {
+	lock_script.map_or(true, |script| &output.lock() == script)
+		&& type_script.map_or(true, |script| output.type_().to_opt().as_ref() == Some(script))
+}
+
+// live cells locked by lock_script, and typed by type_script if it's specified
+pub fn mock_get_live_cells(lock_script: &Script, type_script: Option<&Script>) -> Result<Vec<(OutPoint, CellOutput, Vec<u8>)>, String> {
+	mock_search_cells(Some(lock_script), type_script)
+}
+
+// live cells in the order of committing, every specified script must match
+pub(super) fn mock_search_cells(lock_script: Option<&Script>, type_script: Option<&Script>) -> Result<Vec<(OutPoint, CellOutput, Vec<u8>)>, String> {
+	let mut chain = CHAIN.lock().unwrap();
+	chain.take_rpc_error()?;
+	let mut cells = chain.cells
+		.iter()
+		.filter(|(_, cell)| cell.live && matches_scripts(&cell.output, lock_script, type_script))
+		.map(|(out_point, cell)| ((cell.block_number, cell.tx_index), (out_point.clone(), cell.output.clone(), cell.data.clone())))
+		.collect::<Vec<_>>();
+	cells.sort_by(|a, b| a.0.cmp(&b.0));
+	Ok(cells.into_iter().map(|(_, cell)| cell).collect())
+}
+
+// committed transactions which consume or create cells locked by lock_script, newest first
+pub fn mock_get_transactions(lock_script: &Script, type_script: Option<&Script>) -> Result<Vec<(H256, u64, u32)>, String> {
+	mock_search_transactions(Some(lock_script), type_script)
+}
+
+// committed transactions which consume or create cells matching every specified script, newest first
+pub(super) fn mock_search_transactions(lock_script: Option<&Script>, type_script: Option<&Script>) -> Result<Vec<(H256, u64, u32)>, String> {
+	let mut chain = CHAIN.lock().unwrap();
+	chain.take_rpc_error()?;
+	let mut transactions = vec![];
+	for (hash, value) in &chain.transactions {
+		let block_number = match value.block_number {
+			Some(block_number) => block_number,
+			None               => continue
+		};
+		let matched = |output: &CellOutput| matches_scripts(output, lock_script, type_script);
+		let created = value.tx.raw().outputs().into_iter().any(|output| matched(&output));
+		let consumed = value.tx.raw().inputs().into_iter().any(|input| {
+			chain.cells.get(&input.previous_output()).map_or(false, |cell| matched(&cell.output))
+		});
+		if created || consumed {
+			transactions.push((hash.clone(), block_number, value.tx_index));
+		}
+	}
+	transactions.sort_by(|a, b| (b.1, b.2).cmp(&(a.1, a.2)));
+	Ok(transactions)
+}
\ No newline at end of file
@@ -0,0 +1,311 @@
+use super::mock;
+use ckb_jsonrpc_types::{
+	Uint64, Uint32, JsonBytes
+};
+use serde::Deserialize;
+use serde_json::{
+	json, Value, from_value
+};
+use ckb_types::{
+	H256, prelude::*, packed::{
+		Transaction, OutPoint, Script
+	}
+};
+use std::{
+	thread, io::{
+		Read, Write, BufRead, BufReader
+	}, net::{
+		TcpListener, TcpStream
+	}
+};
+
+// the search_key of ckb indexer, script is searched exactly instead of by prefix
+#[derive(Deserialize)]
+struct SearchKey {
+	script:      ckb_jsonrpc_types::Script,
+	script_type: String,
+	filter:      Option<SearchFilter>
+}
+
+#[derive(Deserialize)]
+struct SearchFilter {
+	script:      Option<ckb_jsonrpc_types::Script>,
+	block_range: Option<[Uint64; 2]>
+}
+
+impl SearchKey {
+	fn scripts(&self) -> Result<(Option<Script>, Option<Script>), String> {
+		let script = Script::from(self.script.clone());
+		let filter = self.filter
+			.as_ref()
+			.and_then(|filter| filter.script.clone())
+			.map(Script::from);
+		match self.script_type.as_str() {
+			"lock" => Ok((Some(script), filter)),
+			"type" => Ok((filter, Some(script))),
+			_      => Err(format!("unknown script_type {}", self.script_type))
+		}
+	}
+
+	fn block_range(&self) -> Option<(u64, u64)> {
+		self.filter
+			.as_ref()
+			.and_then(|filter| filter.block_range.as_ref())
+			.map(|range| (range[0].value(), range[1].value()))
+	}
+}
+
+// host and port of a local http uri, the mock chain never takes the place of a remote node
+pub(super) fn local_address(uri: &str) -> Result<String, String> {
+	let address = uri
+		.trim_start_matches("http://")
+		.split('/')
+		.next()
+		.unwrap_or_default();
+	let host = address.rsplitn(2, ':').last().unwrap_or_default();
+	if !uri.starts_with("http://") || !["127.0.0.1", "localhost", "0.0.0.0"].contains(&host) {
+		return Err(format!("mock chain can't be served on non-local uri {}", uri));
+	}
+	Ok(address.to_string())
+}
+
+// answer json-rpc requests of ckb node and ckb indexer from the mock chain
+pub(super) fn serve(address: &str) -> Result<(), String> {
+	let listener = TcpListener::bind(address).map_err(|err| format!("bind mock chain on {} -> {}", address, err))?;
+	thread::spawn(move || {
+		for stream in listener.incoming().flatten() {
+			thread::spawn(move || {
+				if let Err(err) = handle_connection(stream) {
+					println!("mock chain connection error: {}", err);
+				}
+			});
+		}
+	});
+	Ok(())
+}
+
+// one request per connection, which is closed after responding
+fn handle_connection(stream: TcpStream) -> Result<(), String> {
+	let mut reader = BufReader::new(stream.try_clone().map_err(|err| err.to_string())?);
+	let mut content_length = 0;
+	loop {
+		let mut line = String::new();
+		reader.read_line(&mut line).map_err(|err| err.to_string())?;
+		let line = line.trim_end();
+		if line.is_empty() {
+			break
+		}
+		if let Some((name, value)) = line.split_once(':') {
+			if name.trim().eq_ignore_ascii_case("content-length") {
+				content_length = value.trim().parse::<usize>().map_err(|err| err.to_string())?;
+			}
+		}
+	}
+	let mut body = vec![0u8; content_length];
+	reader.read_exact(&mut body).map_err(|err| err.to_string())?;
+	let response = match serde_json::from_slice::<Value>(&body) {
+		Ok(Value::Array(requests)) => Value::Array(requests.into_iter().map(respond).collect()),
+		Ok(request)                => respond(request),
+		Err(err)                   => json!({
+			"id":      Value::Null,
+			"jsonrpc": "2.0",
+			"error":   { "code": -32700, "message": err.to_string() }
+		})
+	};
+	let body = response.to_string();
+	let mut stream = stream;
+	write!(
+		stream,
+		"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+		body.len(),
+		body
+	).map_err(|err| err.to_string())
+}
+
+fn respond(request: Value) -> Value {
+	let id = request["id"].clone();
+	let method = request["method"].as_str().unwrap_or_default().to_string();
+	let params = match request["params"].clone() {
+		Value::Array(params) => params,
+		_                    => vec![]
+	};
+	let result = if mock::is_mock_chain() {
+		dispatch(&method, params)
+	} else {
+		Err(String::from("mock chain is disabled"))
+	};
+	match result {
+		Ok(result) => json!({ "id": id, "jsonrpc": "2.0", "result": result }),
+		Err(error) => json!({ "id": id, "jsonrpc": "2.0", "error": { "code": -32000, "message": error } })
+	}
+}
+
+fn param<T: serde::de::DeserializeOwned>(params: &[Value], index: usize) -> Result<T, String> {
+	from_value(params.get(index).cloned().unwrap_or(Value::Null)).map_err(|err| format!("invalid param {} -> {}", index, err))
+}
+
+fn header_json(block_number: u64) -> Value {
+	json!(ckb_jsonrpc_types::HeaderView::from(mock::mock_header(block_number)))
+}
+
+// cursor of mock indexer is the big-endian offset of the next object
+fn page<T>(objects: Vec<T>, order: &str, limit: u32, after: Option<JsonBytes>) -> Result<(Vec<T>, JsonBytes), String> {
+	let offset = match after {
+		Some(cursor) if !cursor.is_empty() => {
+			let mut offset = [0u8; 8];
+			offset.copy_from_slice(cursor.as_bytes().get(..8).ok_or(String::from("invalid mock cursor"))?);
+			u64::from_be_bytes(offset) as usize
+		},
+		_ => 0
+	};
+	let objects = match order {
+		"desc" => objects.into_iter().rev().collect::<Vec<_>>(),
+		_      => objects
+	};
+	let objects = objects
+		.into_iter()
+		.skip(offset)
+		.take(limit as usize)
+		.collect::<Vec<_>>();
+	let cursor = JsonBytes::from_vec(((offset + objects.len()) as u64).to_be_bytes().to_vec());
+	Ok((objects, cursor))
+}
+
+fn dispatch(method: &str, params: Vec<Value>) -> Result<Value, String> {
+	let tip = mock::mock_tip();
+	match method {
+		// methods of ckb node
+		"get_tip_block_number" => Ok(json!(Uint64::from(mock::mock_tip_block_number()?))),
+		"get_tip_header" => Ok(header_json(mock::mock_tip_block_number()?)),
+		"get_header_by_number" => {
+			let number: Uint64 = param(&params, 0)?;
+			Ok(if number.value() <= tip { header_json(number.value()) } else { Value::Null })
+		},
+		"get_header" => {
+			let hash: H256 = param(&params, 0)?;
+			Ok(mock::mock_header_by_hash(&hash)?
+				.map_or(Value::Null, |header| json!(ckb_jsonrpc_types::HeaderView::from(header))))
+		},
+		"get_blockchain_info" => Ok(json!({
+			"chain":                     "ckb_dev",
+			"median_time":               Uint64::from(0),
+			"epoch":                     Uint64::from(0),
+			"difficulty":                "0x0",
+			"is_initial_block_download": false,
+			"alerts":                    []
+		})),
+		"get_transaction" => {
+			let hash: H256 = param(&params, 0)?;
+			Ok(match mock::mock_get_transaction(&hash)? {
+				Some((tx, block_number)) => {
+					let tx_status = match block_number {
+						Some(number) => json!({
+							"status":     "committed",
+							"block_hash": Unpack::<H256>::unpack(&mock::mock_header(number).hash())
+						}),
+						None => json!({
+							"status":     "pending",
+							"block_hash": Value::Null
+						})
+					};
+					json!({
+						"transaction": ckb_jsonrpc_types::TransactionView::from(tx.into_view()),
+						"tx_status":   tx_status
+					})
+				},
+				None => Value::Null
+			})
+		},
+		"send_transaction" => {
+			let tx: ckb_jsonrpc_types::Transaction = param(&params, 0)?;
+			Ok(json!(mock::mock_send_transaction(Transaction::from(tx))?))
+		},
+		"get_live_cell" => {
+			let out_point: ckb_jsonrpc_types::OutPoint = param(&params, 0)?;
+			let with_data = param::<Option<bool>>(&params, 1)?.unwrap_or(false);
+			Ok(match mock::mock_get_cell(&OutPoint::from(out_point))? {
+				Some((output, data, true)) => json!({
+					"cell": {
+						"output": ckb_jsonrpc_types::CellOutput::from(output),
+						"data":   if with_data {
+							json!({
+								"content": JsonBytes::from_vec(data.clone()),
+								"hash":    H256(ckb_hash::blake2b_256(&data))
+							})
+						} else {
+							Value::Null
+						}
+					},
+					"status": "live"
+				}),
+				Some(_) => json!({ "cell": Value::Null, "status": "dead" }),
+				None    => json!({ "cell": Value::Null, "status": "unknown" })
+			})
+		},
+
+		// methods of ckb indexer
+		"get_tip" => {
+			let tip = mock::mock_tip_block_number()?;
+			Ok(json!({
+				"block_hash":   Unpack::<H256>::unpack(&mock::mock_header(tip).hash()),
+				"block_number": Uint64::from(tip)
+			}))
+		},
+		"get_cells" => {
+			let search_key: SearchKey = param(&params, 0)?;
+			let (lock_script, type_script) = search_key.scripts()?;
+			let cells = mock::mock_search_cells(lock_script.as_ref(), type_script.as_ref())?;
+			let (cells, last_cursor) = page(cells, &param::<String>(&params, 1)?, param::<Uint32>(&params, 2)?.value(), param(&params, 3)?)?;
+			let objects = cells
+				.into_iter()
+				.map(|(out_point, output, data)| json!({
+					"output":       ckb_jsonrpc_types::CellOutput::from(output),
+					"output_data":  JsonBytes::from_vec(data),
+					"out_point":    ckb_jsonrpc_types::OutPoint::from(out_point),
+					"block_number": Uint64::from(tip),
+					"tx_index":     Uint32::from(0)
+				}))
+				.collect::<Vec<_>>();
+			Ok(json!({ "objects": objects, "last_cursor": last_cursor }))
+		},
+		"get_transactions" => {
+			let search_key: SearchKey = param(&params, 0)?;
+			let (lock_script, type_script) = search_key.scripts()?;
+			let block_range = search_key.block_range();
+			// mock chain lists transactions newest first, which is reversed for ascending order
+			let mut transactions = mock::mock_search_transactions(lock_script.as_ref(), type_script.as_ref())?
+				.into_iter()
+				.filter(|(_, block_number, _)| block_range.map_or(true, |(start, end)| *block_number >= start && *block_number < end))
+				.collect::<Vec<_>>();
+			transactions.reverse();
+			let (transactions, last_cursor) = page(
+				transactions, &param::<String>(&params, 1)?, param::<Uint32>(&params, 2)?.value(), param(&params, 3)?
+			)?;
+			let objects = transactions
+				.into_iter()
+				.map(|(tx_hash, block_number, tx_index)| json!({
+					"tx_hash":      tx_hash,
+					"block_number": Uint64::from(block_number),
+					"tx_index":     Uint32::from(tx_index),
+					"io_index":     Uint32::from(0),
+					"io_type":      "output"
+				}))
+				.collect::<Vec<_>>();
+			Ok(json!({ "objects": objects, "last_cursor": last_cursor }))
+		},
+		"get_cells_capacity" => {
+			let search_key: SearchKey = param(&params, 0)?;
+			let (lock_script, type_script) = search_key.scripts()?;
+			let capacity: u64 = mock::mock_search_cells(lock_script.as_ref(), type_script.as_ref())?
+				.into_iter()
+				.map(|(_, output, _)| Unpack::<u64>::unpack(&output.capacity()))
+				.sum();
+			Ok(json!({
+				"capacity":     Uint64::from(capacity),
+				"block_hash":   Unpack::<H256>::unpack(&mock::mock_header(tip).hash()),
+				"block_number": Uint64::from(tip)
+			}))
+		},
+		_ => Err(format!("method {} isn't supported by mock chain", method))
+	}
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+mod wallet;
+mod tracker;
+mod signer;
+mod fee;
+mod pending;
+mod history;
+mod address;
+mod watcher;
+mod balance;
+mod consolidate;
+mod trade;
+mod swap;
+mod remote;
+mod nft;
+mod mock_node;
+
+pub mod rpc;
+pub mod mock;
+
+pub use wallet::*;
+pub use tracker::*;
+pub use signer::*;
+pub use fee::*;
+pub use pending::*;
+pub use history::*;
+pub use address::*;
+pub use watcher::*;
+pub use balance::*;
+pub use consolidate::*;
+pub use trade::*;
+pub use swap::*;
+pub use remote::*;
\ No newline at end of file
@@ -0,0 +1,191 @@
+use super::{
+	rpc, fee::apply_fee_rate
+};
+use crate::account::active_pubhash;
+use kabletop_ckb_sdk::{
+	config::VARS, ckb::{
+		wallet::keystore, transaction::helper::{
+			sighash_script, wallet_script, nft_script
+		}
+	}
+};
+use ckb_types::{
+	bytes::Bytes, core::{
+		TransactionBuilder, TransactionView, Capacity
+	}, prelude::*, packed::{
+		CellDep, CellInput, CellOutput, OutPoint, Script
+	}
+};
+
+// a plain sighash cell occupies 61 ckb, so change of user must be either none or at least that much
+pub(super) const MIN_CHANGE_CAPACITY: u64 = 6_100_000_000;
+
+// the most fee that user is allowed to pay for its own part of transaction
+pub(super) const FEE_RESERVE: u64 = 1_000_000;
+
+pub(super) type Cell = (OutPoint, CellOutput, Vec<u8>);
+
+pub(super) fn nft_type_script() -> Script {
+	let wallet = wallet_script(keystore::COMPOSER_PUBHASH.to_vec());
+	nft_script(wallet.calc_script_hash().raw_data().to_vec())
+}
+
+pub(super) fn occupied_capacity(output: &CellOutput, data_len: usize) -> Result<u64, String> {
+	let data_capacity = Capacity::bytes(data_len).map_err(|err| err.to_string())?;
+	output
+		.occupied_capacity(data_capacity)
+		.map(|capacity| capacity.as_u64())
+		.map_err(|err| err.to_string())
+}
+
+fn into_nfts(data: &[u8]) -> Vec<[u8; 20]> {
+	data
+		.chunks_exact(20)
+		.map(|hash| {
+			let mut nft = [0u8; 20];
+			nft.copy_from_slice(hash);
+			nft
+		})
+		.collect()
+}
+
+// an nft cell of owner which holds nfts with exactly the capacity it occupies
+pub(super) fn nft_output(owner: &[u8; 20], nfts: &[[u8; 20]]) -> Result<(CellOutput, Bytes), String> {
+	let output = CellOutput::new_builder()
+		.lock(sighash_script(&owner.to_vec()))
+		.type_(Some(nft_type_script()).pack())
+		.build();
+	let capacity = occupied_capacity(&output, nfts.len() * 20)?;
+	Ok((output.as_builder().capacity(capacity.pack()).build(), Bytes::from(nfts.concat())))
+}
+
+// split live cells of owner into nft cells and plain ckb cells, other typed cells are never touched
+pub(super) fn owner_cells(owner: &[u8; 20]) -> Result<(Vec<Cell>, Vec<Cell>), String> {
+	let nft_type = nft_type_script();
+	let mut nft_cells = vec![];
+	let mut ckb_cells = vec![];
+	for cell in rpc::get_live_cells(&sighash_script(&owner.to_vec()), None, usize::MAX)? {
+		match cell.1.type_().to_opt() {
+			Some(script) if script == nft_type => nft_cells.push(cell),
+			None if cell.2.is_empty()          => ckb_cells.push(cell),
+			_                                  => ()
+		}
+	}
+	Ok((nft_cells, ckb_cells))
+}
+
+// pick nft cells until every one of nfts is collected, and return the picked cells along with nfts left in them
+pub(super) fn collect_nfts(nft_cells: &[Cell], nfts: &[[u8; 20]]) -> Result<(Vec<Cell>, Vec<[u8; 20]>), String> {
+	let mut needed = nfts.to_vec();
+	let mut picked = vec![];
+	let mut left = vec![];
+	for cell in nft_cells {
+		if needed.is_empty() {
+			break
+		}
+		let cell_nfts = into_nfts(&cell.2);
+		if !cell_nfts.iter().any(|nft| needed.contains(nft)) {
+			continue
+		}
+		for nft in cell_nfts {
+			match needed.iter().position(|value| value == &nft) {
+				Some(i) => {
+					needed.remove(i);
+				},
+				None => left.push(nft)
+			}
+		}
+		picked.push(cell.clone());
+	}
+	if !needed.is_empty() {
+		return Err(format!("user doesn't own nfts {:?}", needed.iter().map(hex::encode).collect::<Vec<_>>()));
+	}
+	Ok((picked, left))
+}
+
+// user transactions only run sighash lock and nft type scripts, whose deployments are configured in kabletop-ckb-sdk
+// the same as the ones its own builders use, nft script loads the wallet script of composer as well
+fn user_cell_deps() -> Vec<CellDep> {
+	vec![
+		VARS.common.secp256k1_celldep.clone(),
+		VARS.common.nft_celldep.clone(),
+		VARS.common.wallet_celldep.clone()
+	]
+}
+
+// build unsigned transaction which spends inputs of user and creates outputs, plain cells of user are picked from
+// spare cells until the capacity is enough, and the rest goes back to user's change after paying fee of fee rate
+pub(super) fn build_user_transaction(
+	inputs: Vec<Cell>, outputs: Vec<(CellOutput, Bytes)>, spare_cells: &[Cell], fee_rate: u64
+) -> Result<TransactionView, String> {
+	let user_lock = sighash_script(&active_pubhash().to_vec());
+	let outputs_capacity = outputs
+		.iter()
+		.map(|(output, _)| Unpack::<u64>::unpack(&output.capacity()))
+		.sum::<u64>();
+	let target = outputs_capacity + MIN_CHANGE_CAPACITY + FEE_RESERVE;
+	let mut inputs = inputs;
+	let mut capacity = inputs
+		.iter()
+		.map(|(_, output, _)| Unpack::<u64>::unpack(&output.capacity()))
+		.sum::<u64>();
+	for cell in spare_cells {
+		if capacity >= target {
+			break
+		}
+		if inputs.iter().any(|(out_point, _, _)| out_point == &cell.0) {
+			continue
+		}
+		capacity += Unpack::<u64>::unpack(&cell.1.capacity());
+		inputs.push(cell.clone());
+	}
+	if capacity < target {
+		return Err(format!("transaction needs {} shannons, but only {} is spendable", target, capacity));
+	}
+	let tx = TransactionBuilder::default()
+		.cell_deps(user_cell_deps())
+		.inputs(inputs.iter().map(|(out_point, _, _)| CellInput::new(out_point.clone(), 0)))
+		.outputs(outputs.iter().map(|(output, _)| output.clone()))
+		.outputs_data(outputs.iter().map(|(_, data)| data.pack()))
+		.output(CellOutput::new_builder()
+			.lock(user_lock)
+			.capacity((capacity - outputs_capacity).pack())
+			.build())
+		.output_data(Bytes::new().pack())
+		.build();
+	apply_fee_rate(tx, fee_rate)
+}
+
+// build unsigned transaction which takes nfts out of user's nft cells, each recipient gets one nft cell of its nfts,
+// discarded nfts are just dropped, and nfts left in the spent cells are put back into one nft cell of user
+pub(super) fn build_tx_move_nfts(
+	transfers: Vec<([u8; 20], Vec<[u8; 20]>)>, discards: Vec<[u8; 20]>, fee_rate: u64
+) -> Result<TransactionView, String> {
+	let user = active_pubhash();
+	let mut moved = discards;
+	let mut recipients: Vec<([u8; 20], Vec<[u8; 20]>)> = vec![];
+	for (to, nfts) in transfers {
+		// nfts sent to user itself just stay in its nft cells
+		if to == user || nfts.is_empty() {
+			continue
+		}
+		moved.extend(nfts.iter().cloned());
+		match recipients.iter_mut().find(|(recipient, _)| recipient == &to) {
+			Some((_, value)) => value.extend(nfts),
+			None             => recipients.push((to, nfts))
+		}
+	}
+	if moved.is_empty() {
+		return Err(String::from("no nft is taken out of user's nft cells"));
+	}
+	let (nft_cells, ckb_cells) = owner_cells(&user)?;
+	let (inputs, left) = collect_nfts(&nft_cells, &moved)?;
+	let mut outputs = recipients
+		.iter()
+		.map(|(to, nfts)| nft_output(to, nfts))
+		.collect::<Result<Vec<_>, _>>()?;
+	if !left.is_empty() {
+		outputs.push(nft_output(&user, &left)?);
+	}
+	build_user_transaction(inputs, outputs, &ckb_cells, fee_rate)
+}
\ No newline at end of file
@@ -0,0 +1,180 @@
+use super::{
+	rpc, fee::transaction_fee, wallet::{
+		WalletAction, build_wallet_transaction, push_transaction
+	}
+};
+use crate::account::{
+	active_pubhash, active_signer
+};
+use kabletop_ckb_sdk::ckb::{
+	wallet::keystore, transaction::helper::{
+		sighash_script, wallet_script, nft_script
+	}
+};
+use ckb_types::{
+	H256, core::TransactionView, prelude::*, packed::{
+		CellOutput, Script
+	}
+};
+use std::{
+	sync::Mutex, thread, collections::HashMap, time::{
+		Duration, Instant
+	}
+};
+
+// built transactions are dropped once their cells are likely to be spent by others, or too many are left behind
+const PENDING_TTL: u64 = 600;
+const MAX_PENDING: usize = 16;
+
+lazy_static! {
+	// built transactions along with when and for which account they were built
+	static ref PENDING: Mutex<(u64, HashMap<u64, (Instant, [u8; 20], TransactionView)>)> = Mutex::new((0, HashMap::new()));
+}
+
+// one input or output cell of the transaction, nfts are decoded from cell data if it's an nft cell
+#[derive(Clone)]
+pub struct CellSummary {
+	pub capacity:  u64,
+	pub lock_hash: [u8; 32],
+	pub lock_code: [u8; 32],
+	pub is_user:   bool,
+	pub nfts:      Vec<String>
+}
+
+// what the transaction will do to user's assets, which is shown to player before signing
+#[derive(Clone)]
+pub struct TransactionSummary {
+	pub tx_hash:         H256,
+	pub inputs:          Vec<CellSummary>,
+	pub outputs:         Vec<CellSummary>,
+	pub nfts_received:   Vec<String>,
+	pub nfts_sent:       Vec<String>,
+	pub capacity_change: i64,
+	pub fee:             u64
+}
+
+fn summarize_cell(output: &CellOutput, data: &[u8], user_lock: &Script, nft_type: &Script) -> CellSummary {
+	let nfts = if output.type_().to_opt().as_ref() == Some(nft_type) {
+		data.chunks_exact(20).map(hex::encode).collect()
+	} else {
+		vec![]
+	};
+	CellSummary {
+		capacity:  output.capacity().unpack(),
+		lock_hash: output.lock().calc_script_hash().unpack(),
+		lock_code: output.lock().code_hash().unpack(),
+		is_user:   &output.lock() == user_lock,
+		nfts
+	}
+}
+
+// remove every nft in `other` from `nfts` once, the remains are the nfts which are really moved
+fn subtract_nfts(nfts: &[String], other: &[String]) -> Vec<String> {
+	let mut other = other.to_vec();
+	nfts
+		.iter()
+		.filter(|nft| match other.iter().position(|value| value == *nft) {
+			Some(i) => {
+				other.remove(i);
+				false
+			},
+			None => true
+		})
+		.cloned()
+		.collect()
+}
+
+// derive the summary from transaction, the input cells are fetched from ckb node
+pub fn summarize_transaction(tx: &TransactionView) -> Result<TransactionSummary, String> {
+	let user_lock = sighash_script(&active_pubhash().to_vec());
+	let nft_type = {
+		let wallet = wallet_script(keystore::COMPOSER_PUBHASH.to_vec());
+		nft_script(wallet.calc_script_hash().raw_data().to_vec())
+	};
+	let mut inputs = vec![];
+	for input in tx.inputs() {
+		let (output, data) = rpc::get_cell(&input.previous_output())?;
+		inputs.push(summarize_cell(&output, &data, &user_lock, &nft_type));
+	}
+	let outputs = tx
+		.outputs_with_data_iter()
+		.map(|(output, data)| summarize_cell(&output, &data, &user_lock, &nft_type))
+		.collect::<Vec<_>>();
+	let user_nfts = |cells: &Vec<CellSummary>| cells
+		.iter()
+		.filter(|cell| cell.is_user)
+		.map(|cell| cell.nfts.clone())
+		.flatten()
+		.collect::<Vec<_>>();
+	let user_capacity = |cells: &Vec<CellSummary>| cells
+		.iter()
+		.filter(|cell| cell.is_user)
+		.map(|cell| cell.capacity as i64)
+		.sum::<i64>();
+	let (nfts_in, nfts_out) = (user_nfts(&inputs), user_nfts(&outputs));
+	Ok(TransactionSummary {
+		tx_hash:         tx.hash().unpack(),
+		nfts_received:   subtract_nfts(&nfts_out, &nfts_in),
+		nfts_sent:       subtract_nfts(&nfts_in, &nfts_out),
+		capacity_change: user_capacity(&outputs) - user_capacity(&inputs),
+		fee:             transaction_fee(tx)?,
+		inputs,
+		outputs
+	})
+}
+
+// build unsigned transaction of wallet action and keep it under a handle until it's submitted, cancelled or expired
+pub fn build_transaction(action: WalletAction, fee_rate: Option<u64>) -> Result<(u64, TransactionSummary), String> {
+	let builder = active_pubhash();
+	let tx = build_wallet_transaction(action, fee_rate)?;
+	let summary = summarize_transaction(&tx)?;
+	let mut pending = PENDING.lock().unwrap();
+	pending.1.retain(|_, (built, _, _)| built.elapsed() < Duration::from_secs(PENDING_TTL));
+	if pending.1.len() >= MAX_PENDING {
+		let oldest = pending.1
+			.iter()
+			.min_by_key(|(_, (built, _, _))| *built)
+			.map(|(handle, _)| *handle)
+			.unwrap();
+		pending.1.remove(&oldest);
+	}
+	pending.0 += 1;
+	let handle = pending.0;
+	pending.1.insert(handle, (Instant::now(), builder, tx));
+	Ok((handle, summary))
+}
+
+// sign the built transaction with user's key and push it to ckb network, which must still be the account the
+// transaction was built for
+pub fn sign_and_submit<F>(handle: u64, f: F)
+where
+	F: Fn(Result<H256, String>) + Send + 'static
+{
+	let tx = PENDING.lock().unwrap().1.remove(&handle);
+	thread::spawn(move || {
+		let (built, builder, tx) = match tx {
+			Some((built, _, _)) if built.elapsed() >= Duration::from_secs(PENDING_TTL) => {
+				return f(Err(format!("built transaction for handle {} is expired, please build it again", handle)))
+			},
+			Some(pending) => pending,
+			None          => return f(Err(format!("no built transaction for handle {}", handle)))
+		};
+		let signer = active_signer();
+		if signer.pubhash() != builder {
+			// the handle is kept so that it can still be submitted after switching back
+			PENDING.lock().unwrap().1.insert(handle, (built, builder, tx));
+			return f(Err(format!(
+				"transaction for handle {} is built for account {}, please switch back to it", handle, hex::encode(builder)
+			)))
+		}
+		match signer.sign_tx(tx) {
+			Ok(tx)   => f(push_transaction(tx)),
+			Err(err) => f(Err(err))
+		}
+	});
+}
+
+// drop the built transaction without signing it
+pub fn cancel_transaction(handle: u64) -> bool {
+	PENDING.lock().unwrap().1.remove(&handle).is_some()
+}
\ No newline at end of file
@@ -0,0 +1,362 @@
+use super::signer::{
+	Signer, sign_sighash_tx
+};
+use kabletop_ckb_sdk::ckb::transaction::channel::protocol::{
+	Round, Args
+};
+use ckb_types::{
+	H256, core::TransactionView, prelude::*, packed::{
+		Byte32, Transaction
+	}
+};
+use ckb_jsonrpc_types::JsonBytes;
+use ckb_crypto::secp::Signature;
+use molecule::prelude::Entity;
+use serde::{
+	Deserialize, Serialize
+};
+use serde_json::{
+	json, Value
+};
+use std::{
+	sync::Mutex, collections::HashMap, time::Duration, net::{
+		TcpStream, TcpListener
+	}, io::{
+		BufRead, BufReader, Write
+	}
+};
+
+// how long game waits for companion to approve one signing
+const APPROVAL_TIMEOUT: u64 = 120;
+
+const CKB_SHANNONS: u64 = 100_000_000;
+
+// rounds of channel are signed without asking once the channel matches, none of channel means any channel the
+// companion has signed, and stake is the staking ckb of user in that channel
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ApprovalPolicy {
+	pub channel:         Option<[u8; 32]>,
+	pub max_staking_ckb: u64
+}
+
+impl ApprovalPolicy {
+	// channels are recorded with staking in shannons as lock args hold it, so the limit is converted here only
+	fn matches(&self, script_hash: &[u8; 32], staking_shannons: u64) -> bool {
+		self.channel.map_or(true, |channel| &channel == script_hash)
+			&& staking_shannons <= self.max_staking_ckb.saturating_mul(CKB_SHANNONS)
+	}
+}
+
+// json-rpc requests from game to companion, one request per line
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+enum SignRequest {
+	Authenticate {
+		token: String
+	},
+	GetPubhash,
+	SignMessage {
+		message: H256
+	},
+	SignChannelTx {
+		tx:             ckb_jsonrpc_types::TransactionView,
+		staking_ckb:    u64,
+		bet_ckb:        u64,
+		max_nfts_count: u8,
+		nfts:           Vec<[u8; 20]>
+	},
+	SignRound {
+		script_hash:   [u8; 32],
+		signed_rounds: Vec<(JsonBytes, JsonBytes)>,
+		round:         JsonBytes
+	},
+	RequestAutoApproval {
+		policy: ApprovalPolicy
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+struct Request {
+	id: u64,
+	#[serde(flatten)]
+	request: SignRequest
+}
+
+#[derive(Serialize, Deserialize)]
+struct Response {
+	id:     u64,
+	result: Option<Value>,
+	error:  Option<String>
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(value: Value) -> Result<T, String> {
+	serde_json::from_value(value).map_err(|err| err.to_string())
+}
+
+fn into_signature(value: Value) -> Result<Signature, String> {
+	let signature: JsonBytes = from_json(value)?;
+	Signature::from_slice(signature.as_bytes()).map_err(|err| err.to_string())
+}
+
+fn format_ckb(shannons: u64) -> String {
+	format!("{}.{:08}", shannons / CKB_SHANNONS, shannons % CKB_SHANNONS)
+}
+
+// compare without returning early, so the time taken doesn't tell how much of a guessed token is right
+fn token_matches(token: &str, expected: &str) -> bool {
+	token.len() == expected.len() && token
+		.bytes()
+		.zip(expected.bytes())
+		.fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// staking, deck size and nfts of signer's side and the opposite pkhash, read from the lock args of kabletop output
+// instead of what game reports, since game is exactly what companion doesn't trust
+fn channel_side(tx: &TransactionView, pubhash: &[u8; 20]) -> Result<(u64, u8, Vec<[u8; 20]>, [u8; 20]), String> {
+	let kabletop = tx.output(0).ok_or(String::from("channel tx has no kabletop output"))?;
+	let lock_args: Vec<u8> = kabletop.lock().args().unpack();
+	let args = Args::from_slice(&lock_args).map_err(|err| format!("invalid kabletop lock args -> {}", err))?;
+	let user1_pkhash: [u8; 20] = args.user1_pkhash().into();
+	let user2_pkhash: [u8; 20] = args.user2_pkhash().into();
+	let (nfts, opposite) = if &user1_pkhash == pubhash {
+		(args.user1_nfts(), user2_pkhash)
+	} else if &user2_pkhash == pubhash {
+		(args.user2_nfts(), user1_pkhash)
+	} else {
+		return Err(String::from("companion's account isn't a user of this channel"));
+	};
+	Ok((args.user_staking_ckb().into(), u8::from(args.user_deck_size()), nfts.into(), opposite))
+}
+
+// signer whose key is kept by a companion process listening on a local socket, every signing waits for the companion
+// to approve it unless an approval policy of the companion covers it, and a connection is only served once it proves
+// the token companion was started with
+pub struct RemoteSigner {
+	pubhash:    [u8; 20],
+	connection: Mutex<(u64, BufReader<TcpStream>)>
+}
+
+impl RemoteSigner {
+	pub fn connect(address: &str, token: &str) -> Result<Self, String> {
+		let stream = TcpStream::connect(address).map_err(|err| format!("connect signer {} -> {}", address, err))?;
+		stream
+			.set_read_timeout(Some(Duration::from_secs(APPROVAL_TIMEOUT)))
+			.map_err(|err| err.to_string())?;
+		let mut signer = RemoteSigner {
+			pubhash:    [0u8; 20],
+			connection: Mutex::new((0, BufReader::new(stream)))
+		};
+		signer.call(SignRequest::Authenticate {
+			token: String::from(token)
+		})?;
+		signer.pubhash = from_json(signer.call(SignRequest::GetPubhash)?)?;
+		Ok(signer)
+	}
+
+	fn call(&self, request: SignRequest) -> Result<Value, String> {
+		let mut connection = self.connection.lock().unwrap();
+		connection.0 += 1;
+		let id = connection.0;
+		let mut line = serde_json::to_string(&Request { id, request }).map_err(|err| err.to_string())?;
+		line.push('\n');
+		connection.1
+			.get_mut()
+			.write_all(line.as_bytes())
+			.map_err(|err| format!("write signer -> {}", err))?;
+		// responses of calls which have timed out may still arrive late, they're answered to nobody and skipped
+		let response = loop {
+			let mut line = String::new();
+			match connection.1.read_line(&mut line) {
+				Ok(0)    => return Err(String::from("signer closed connection")),
+				Ok(_)    => (),
+				Err(err) => return Err(format!("read signer -> {}", err))
+			}
+			let response: Response = serde_json::from_str(line.as_str()).map_err(|err| err.to_string())?;
+			if response.id == id {
+				break response
+			}
+			if response.id > id {
+				return Err(format!("signer responsed {}, but {} is expected", response.id, id));
+			}
+		};
+		match (response.result, response.error) {
+			(_, Some(error))     => Err(error),
+			(Some(result), None) => Ok(result),
+			(None, None)         => Err(String::from("signer responsed nothing"))
+		}
+	}
+}
+
+impl Signer for RemoteSigner {
+	fn pubhash(&self) -> [u8; 20] {
+		self.pubhash
+	}
+
+	fn sign_message(&self, message: &H256) -> Result<Signature, String> {
+		into_signature(self.call(SignRequest::SignMessage {
+			message: message.clone()
+		})?)
+	}
+
+	fn sign_tx(&self, tx: TransactionView) -> Result<TransactionView, String> {
+		sign_sighash_tx(tx, self)
+	}
+
+	fn sign_channel_tx(
+		&self, tx: TransactionView, staking_ckb: u64, bet_ckb: u64, max_nfts_count: u8, nfts: Vec<[u8; 20]>
+	) -> Result<TransactionView, String> {
+		let tx: ckb_jsonrpc_types::TransactionView = from_json(self.call(SignRequest::SignChannelTx {
+			tx: tx.into(),
+			staking_ckb,
+			bet_ckb,
+			max_nfts_count,
+			nfts
+		})?)?;
+		let tx: Transaction = tx.inner.into();
+		Ok(tx.into_view())
+	}
+
+	fn sign_round(&self, script_hash: Byte32, signed_rounds: Vec<(Round, Signature)>, round: Round) -> Result<Signature, String> {
+		into_signature(self.call(SignRequest::SignRound {
+			script_hash:   Unpack::<H256>::unpack(&script_hash).0,
+			signed_rounds: signed_rounds
+				.into_iter()
+				.map(|(round, signature)| (JsonBytes::from_vec(round.as_slice().to_vec()), JsonBytes::from_vec(signature.serialize())))
+				.collect(),
+			round:         JsonBytes::from_vec(round.as_slice().to_vec())
+		})?)
+	}
+
+	fn request_auto_approval(&self, policy: ApprovalPolicy) -> Result<(), String> {
+		self.call(SignRequest::RequestAutoApproval { policy }).map(|_| ())
+	}
+}
+
+// the companion side, which serves one game at a time with a local signer, a connection must authenticate with token
+// before anything else, and signings out of policies are passed to approve with a readable description and declined if
+// it returns false
+pub fn serve_signer<S, F>(address: &str, token: &str, signer: S, mut policies: Vec<ApprovalPolicy>, approve: F) -> Result<(), String>
+where
+	S: Signer,
+	F: Fn(&str) -> bool
+{
+	let listener = TcpListener::bind(address).map_err(|err| format!("bind {} -> {}", address, err))?;
+	// staking shannons of channels signed by companion, so that their rounds can be checked against policies
+	let mut channels = HashMap::new();
+	for stream in listener.incoming() {
+		let stream = stream.map_err(|err| err.to_string())?;
+		let mut writer = stream.try_clone().map_err(|err| err.to_string())?;
+		let mut authenticated = false;
+		for line in BufReader::new(stream).lines() {
+			let line = match line {
+				Ok(line) => line,
+				Err(_)   => break
+			};
+			let request: Request = match serde_json::from_str(line.as_str()) {
+				Ok(request) => request,
+				Err(err)    => {
+					println!("skip invalid signing request: {}", err);
+					continue
+				}
+			};
+			let declined = || Err(String::from("signing is declined by companion"));
+			let result = match request.request {
+				SignRequest::Authenticate { token: value } => {
+					authenticated = token_matches(value.as_str(), token);
+					if authenticated {
+						Ok(json!(true))
+					} else {
+						Err(String::from("invalid companion token"))
+					}
+				},
+				_ if !authenticated => Err(String::from("companion requires authentication")),
+				SignRequest::GetPubhash => Ok(json!(signer.pubhash())),
+				SignRequest::SignMessage { message } => {
+					if approve(format!("sign message {}", message).as_str()) {
+						signer.sign_message(&message).map(|signature| json!(JsonBytes::from_vec(signature.serialize())))
+					} else {
+						declined()
+					}
+				},
+				SignRequest::SignChannelTx { tx, staking_ckb, bet_ckb, max_nfts_count, nfts } => {
+					let tx: Transaction = tx.inner.into();
+					let tx = tx.into_view();
+					// bet isn't held by lock args, but the winner can't take more than loser's staking anyway
+					let checked = channel_side(&tx, &signer.pubhash()).and_then(|(staking, deck_size, channel_nfts, opposite)| {
+						if staking != staking_ckb || deck_size != max_nfts_count || channel_nfts != nfts {
+							Err(String::from("channel tx mismatches the staking, deck size or nfts reported by game"))
+						} else if bet_ckb > staking {
+							Err(String::from("bet exceeds staking of channel"))
+						} else {
+							Ok((staking, opposite))
+						}
+					});
+					match checked {
+						Ok((staking, opposite)) => {
+							let description = format!(
+								"open channel with {} staking {} ckb and playing nfts [{}]",
+								hex::encode(opposite),
+								format_ckb(staking),
+								nfts.iter().map(hex::encode).collect::<Vec<_>>().join(", ")
+							);
+							if approve(description.as_str()) {
+								let script_hash = Unpack::<H256>::unpack(&tx.output(0).unwrap().calc_lock_hash()).0;
+								signer
+									.sign_channel_tx(tx, staking, bet_ckb, max_nfts_count, nfts)
+									.map(|tx| {
+										channels.insert(script_hash, staking);
+										json!(ckb_jsonrpc_types::TransactionView::from(tx))
+									})
+							} else {
+								declined()
+							}
+						},
+						Err(err) => Err(err)
+					}
+				},
+				SignRequest::SignRound { script_hash, signed_rounds, round } => {
+					let auto = channels
+						.get(&script_hash)
+						.map_or(false, |&staking_ckb| policies.iter().any(|policy| policy.matches(&script_hash, staking_ckb)));
+					if auto || approve(format!("sign round {} of channel {}", signed_rounds.len() + 1, hex::encode(script_hash)).as_str()) {
+						let signed_rounds = signed_rounds
+							.into_iter()
+							.map(|(round, signature)| {
+								let round = Round::from_slice(round.as_bytes()).map_err(|err| err.to_string())?;
+								let signature = Signature::from_slice(signature.as_bytes()).map_err(|err| err.to_string())?;
+								Ok((round, signature))
+							})
+							.collect::<Result<Vec<_>, String>>();
+						let round = Round::from_slice(round.as_bytes()).map_err(|err| err.to_string());
+						signed_rounds
+							.and_then(|signed_rounds| round.map(|round| (signed_rounds, round)))
+							.and_then(|(signed_rounds, round)| signer.sign_round(script_hash.pack(), signed_rounds, round))
+							.map(|signature| json!(JsonBytes::from_vec(signature.serialize())))
+					} else {
+						declined()
+					}
+				},
+				SignRequest::RequestAutoApproval { policy } => {
+					let channel = policy.channel.map_or(String::from("any channel"), |channel| format!("channel {}", hex::encode(channel)));
+					let description = format!("auto sign rounds of {} staking up to {} ckb", channel, policy.max_staking_ckb);
+					if approve(description.as_str()) {
+						policies.push(policy);
+						Ok(json!(true))
+					} else {
+						declined()
+					}
+				}
+			};
+			let response = match result {
+				Ok(result) => Response { id: request.id, result: Some(result), error: None },
+				Err(error) => Response { id: request.id, result: None, error: Some(error) }
+			};
+			let mut line = serde_json::to_string(&response).map_err(|err| err.to_string())?;
+			line.push('\n');
+			if writer.write_all(line.as_bytes()).is_err() || !authenticated {
+				break
+			}
+		}
+	}
+	Ok(())
+}
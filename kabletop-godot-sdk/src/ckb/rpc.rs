@@ -0,0 +1,224 @@
+use super::mock;
+use kabletop_ckb_sdk::{
+	config::VARS, ckb::rpc::methods
+};
+use ckb_jsonrpc_types::{
+	TransactionWithStatus, HeaderView, Status, Uint64, Uint32, JsonBytes, ChainInfo
+};
+use serde::{
+	Deserialize, de::DeserializeOwned
+};
+use serde_json::{
+	json, Value
+};
+use ckb_types::{
+	H256, prelude::*, core::TransactionView, packed::{
+		Transaction, CellOutput, OutPoint, Script
+	}
+};
+
+// the status of one transaction from the view of ckb node
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ChainStatus {
+	Unknown,
+	Pending,
+	Proposed,
+	Committed(u64)
+}
+
+// call json-rpc method of ckb node or ckb indexer which isn't wrapped by kabletop-ckb-sdk
+pub fn call<T: DeserializeOwned>(uri: &str, method: &str, params: Value) -> Result<T, String> {
+	mock::serve_if_enabled();
+	let request = json!({
+		"id":      1,
+		"jsonrpc": "2.0",
+		"method":  method,
+		"params":  params
+	});
+	let mut response: Value = reqwest::blocking::Client::new()
+		.post(uri)
+		.json(&request)
+		.send()
+		.map_err(|err| format!("{} -> {}", method, err))?
+		.json()
+		.map_err(|err| format!("{} -> {}", method, err))?;
+	if let Some(error) = response.get("error") {
+		return Err(format!("{} -> {}", method, error));
+	}
+	serde_json::from_value(response["result"].take()).map_err(|err| format!("{} -> {}", method, err))
+}
+
+pub fn get_tip_block_number() -> Result<u64, String> {
+	let number: Uint64 = call(&VARS.common.ckb_uri, "get_tip_block_number", json!([]))?;
+	Ok(number.value())
+}
+
+// one cell-level record of indexer get_transactions, cells of one transaction share the same tx_hash
+#[derive(Deserialize)]
+pub struct IndexerTx {
+	pub tx_hash:      H256,
+	pub block_number: Uint64,
+	pub tx_index:     Uint32,
+	pub io_index:     Uint32,
+	pub io_type:      String
+}
+
+#[derive(Deserialize)]
+pub struct IndexerPage<T> {
+	pub objects:     Vec<T>,
+	pub last_cursor: JsonBytes
+}
+
+// get the transaction by hash, no matter it's committed or still in tx-pool
+pub fn get_transaction(hash: &H256) -> Result<Transaction, String> {
+	let tx: Option<TransactionWithStatus> = call(&VARS.common.ckb_uri, "get_transaction", json!([hash]))?;
+	Ok(Transaction::from(tx.ok_or(format!("transaction ({}) not found", hash))?.transaction.inner))
+}
+
+// search transactions which consume or create cells locked by lock_script from ckb indexer, newest first, and
+// only the cells of type_script are searched if it's specified
+pub fn get_transactions(
+	lock_script: &Script, type_script: Option<&Script>, block_range: Option<(u64, u64)>, limit: u32, after: Option<JsonBytes>
+) -> Result<IndexerPage<IndexerTx>, String> {
+	let mut search_key = match type_script {
+		Some(type_script) => json!({
+			"script":      ckb_jsonrpc_types::Script::from(type_script.clone()),
+			"script_type": "type",
+			"filter":      {
+				"script": ckb_jsonrpc_types::Script::from(lock_script.clone())
+			}
+		}),
+		None => json!({
+			"script":      ckb_jsonrpc_types::Script::from(lock_script.clone()),
+			"script_type": "lock"
+		})
+	};
+	if let Some((start, end)) = block_range {
+		search_key["filter"]["block_range"] = json!([Uint64::from(start), Uint64::from(end)]);
+	}
+	call(&VARS.common.ckb_indexer_uri, "get_transactions", json!([search_key, "desc", Uint32::from(limit), after]))
+}
+
+// unix timestamp in seconds of the block at block_number
+pub fn get_block_timestamp(block_number: u64) -> Result<u64, String> {
+	let header: Option<HeaderView> = call(&VARS.common.ckb_uri, "get_header_by_number", json!([Uint64::from(block_number)]))?;
+	header
+		.map(|header| header.inner.timestamp.value() / 1000)
+		.ok_or(format!("block {} not found", block_number))
+}
+
+// the chain name of connected ckb node, "ckb" for mainnet and "ckb_testnet" for testnet
+pub fn get_chain_name() -> Result<String, String> {
+	let info: ChainInfo = call(&VARS.common.ckb_uri, "get_blockchain_info", json!([]))?;
+	Ok(info.chain)
+}
+
+#[derive(Deserialize)]
+struct IndexerCell {
+	output:      ckb_jsonrpc_types::CellOutput,
+	output_data: JsonBytes,
+	out_point:   ckb_jsonrpc_types::OutPoint
+}
+
+#[derive(Deserialize)]
+struct IndexerCapacity {
+	capacity: Uint64
+}
+
+fn cells_search_key(lock_script: &Script, type_script: Option<&Script>) -> Value {
+	let mut search_key = json!({
+		"script":      ckb_jsonrpc_types::Script::from(lock_script.clone()),
+		"script_type": "lock"
+	});
+	if let Some(type_script) = type_script {
+		search_key["filter"] = json!({
+			"script": ckb_jsonrpc_types::Script::from(type_script.clone())
+		});
+	}
+	search_key
+}
+
+// push transaction into tx-pool of ckb node
+pub fn send_transaction(tx: &TransactionView) -> Result<H256, String> {
+	mock::serve_if_enabled();
+	methods::send_transaction(tx.data()).map_err(|err| err.to_string())
+}
+
+// one page of live cells after the cursor, the returned cursor is none if there is no more page
+pub fn get_live_cells_page(
+	lock_script: &Script, type_script: Option<&Script>, page_size: u32, after: Option<JsonBytes>
+) -> Result<(Vec<(OutPoint, CellOutput, Vec<u8>)>, Option<JsonBytes>), String> {
+	let page: IndexerPage<IndexerCell> = call(
+		&VARS.common.ckb_indexer_uri, "get_cells", json!([cells_search_key(lock_script, type_script), "asc", Uint32::from(page_size), after])
+	)?;
+	let cursor = if page.objects.len() < page_size as usize {
+		None
+	} else {
+		Some(page.last_cursor)
+	};
+	let cells = page.objects
+		.into_iter()
+		.map(|cell| (cell.out_point.into(), cell.output.into(), cell.output_data.into_bytes().to_vec()))
+		.collect();
+	Ok((cells, cursor))
+}
+
+// live cells locked by lock_script, and typed by type_script if it's specified, at most limit cells are returned
+pub fn get_live_cells(
+	lock_script: &Script, type_script: Option<&Script>, limit: usize
+) -> Result<Vec<(OutPoint, CellOutput, Vec<u8>)>, String> {
+	let mut cells = vec![];
+	let mut after: Option<JsonBytes> = None;
+	while cells.len() < limit {
+		let page_size = std::cmp::min(limit - cells.len(), 100) as u32;
+		let (page, cursor) = get_live_cells_page(lock_script, type_script, page_size, after)?;
+		cells.extend(page);
+		match cursor {
+			Some(cursor) => after = Some(cursor),
+			None         => break
+		}
+	}
+	Ok(cells)
+}
+
+// total capacity of live cells locked by lock_script
+pub fn get_capacity(lock_script: &Script) -> Result<u64, String> {
+	let capacity: Option<IndexerCapacity> = call(
+		&VARS.common.ckb_indexer_uri, "get_cells_capacity", json!([cells_search_key(lock_script, None)])
+	)?;
+	Ok(capacity.map_or(0, |value| value.capacity.value()))
+}
+
+// get the cell which out_point points to, no matter it's live or dead
+pub fn get_cell(out_point: &OutPoint) -> Result<(CellOutput, Vec<u8>), String> {
+	let tx_hash: H256 = out_point.tx_hash().unpack();
+	let index: u32 = out_point.index().unpack();
+	let tx = get_transaction(&tx_hash)?;
+	let output = tx.raw().outputs().get(index as usize);
+	let data = tx.raw().outputs_data().get(index as usize);
+	match (output, data) {
+		(Some(output), Some(data)) => Ok((output, data.raw_data().to_vec())),
+		_                          => Err(format!("cell {}:{} not found", tx_hash, index))
+	}
+}
+
+// search transaction in ckb node and tell which stage it has reached
+pub fn get_transaction_status(hash: &H256) -> Result<ChainStatus, String> {
+	let tx: Option<TransactionWithStatus> = call(&VARS.common.ckb_uri, "get_transaction", json!([hash]))?;
+	let status = match tx {
+		Some(tx) => match tx.tx_status.status {
+			Status::Pending   => ChainStatus::Pending,
+			Status::Proposed  => ChainStatus::Proposed,
+			Status::Committed => {
+				let block_hash = tx.tx_status.block_hash.ok_or(String::from("committed transaction without block_hash"))?;
+				let header: Option<HeaderView> = call(&VARS.common.ckb_uri, "get_header", json!([block_hash]))?;
+				match header {
+					Some(header) => ChainStatus::Committed(header.inner.number.value()),
+					None         => ChainStatus::Pending
+				}
+			}
+		},
+		None => ChainStatus::Unknown
+	};
+	Ok(status)
+}
@@ -0,0 +1,207 @@
+use super::{
+	rpc, remote::ApprovalPolicy
+};
+use kabletop_ckb_sdk::{
+	config::VARS, ckb::transaction::{
+		helper::sighash_script, channel::{
+			interact as channel, protocol::Round
+		}
+	}
+};
+use ckb_types::{
+	H256, bytes::Bytes, core::TransactionView, prelude::*, packed::{
+		WitnessArgs, BytesOpt, Byte32
+	}
+};
+use ckb_crypto::secp::{
+	Privkey, Signature
+};
+use ckb_hash::new_blake2b;
+use zeroize::{
+	Zeroize, Zeroizing
+};
+
+// everything which needs the private key of user goes through signer, so the key never leaves it
+pub trait Signer: Send + Sync {
+	// the blake160 hash of public key, which is the args of user's sighash lock_script
+	fn pubhash(&self) -> [u8; 20];
+
+	// sign a 32 bytes message in recoverable form
+	fn sign_message(&self, message: &H256) -> Result<Signature, String>;
+
+	// sign all inputs locked by user's sighash lock_script
+	fn sign_tx(&self, tx: TransactionView) -> Result<TransactionView, String>;
+
+	// sign the transaction which opens kabletop channel with user's staking and nfts
+	fn sign_channel_tx(
+		&self, tx: TransactionView, staking_ckb: u64, bet_ckb: u64, max_nfts_count: u8, nfts: Vec<[u8; 20]>
+	) -> Result<TransactionView, String>;
+
+	// sign the next round of kabletop channel on top of all signed rounds
+	fn sign_round(&self, script_hash: Byte32, signed_rounds: Vec<(Round, Signature)>, round: Round) -> Result<Signature, String>;
+
+	// ask signer to sign rounds covered by policy without approval, a local signer never asks so it has nothing to do
+	fn request_auto_approval(&self, _policy: ApprovalPolicy) -> Result<(), String> {
+		Ok(())
+	}
+}
+
+// signer with a local private key, either the one configured in kabletop-ckb-sdk or a secret unlocked from keystore,
+// whose secret is zeroized once the signer is dropped
+pub struct KeySigner {
+	pubhash: [u8; 20],
+	secret:  Zeroizing<[u8; 32]>
+}
+
+impl KeySigner {
+	// the configured key is copied out of kabletop-ckb-sdk, which still keeps its own copy in VARS for as long as the
+	// process lives, so only keystore accounts keep their key out of global memory
+	pub fn configured() -> Self {
+		let mut key: H256 = VARS.common.user_key.privkey.clone().into();
+		let mut secret = Zeroizing::new([0u8; 32]);
+		secret.copy_from_slice(key.as_bytes());
+		key.0.zeroize();
+		KeySigner {
+			pubhash: VARS.common.user_key.pubhash,
+			secret
+		}
+	}
+
+	pub fn from_secret(secret: Zeroizing<[u8; 32]>) -> Result<Self, String> {
+		let pubkey = Privkey::from_slice(&secret[..])
+			.pubkey()
+			.map_err(|err| err.to_string())?;
+		let mut pubhash = [0u8; 20];
+		pubhash.copy_from_slice(&ckb_hash::blake2b_256(pubkey.serialize())[..20]);
+		Ok(KeySigner {
+			pubhash,
+			secret
+		})
+	}
+
+	// a Privkey is made from secret for each signing because the sdk signs with it, it's dropped right after but
+	// isn't guaranteed to be zeroized, nor are the copies made inside secp256k1
+	fn with_privkey<R, F: FnOnce(&Privkey) -> R>(&self, f: F) -> R {
+		f(&Privkey::from_slice(&self.secret[..]))
+	}
+}
+
+impl Signer for KeySigner {
+	fn pubhash(&self) -> [u8; 20] {
+		self.pubhash
+	}
+
+	fn sign_message(&self, message: &H256) -> Result<Signature, String> {
+		self.with_privkey(|privkey| privkey.sign_recoverable(message).map_err(|err| err.to_string()))
+	}
+
+	fn sign_tx(&self, tx: TransactionView) -> Result<TransactionView, String> {
+		sign_sighash_tx(tx, self)
+	}
+
+	fn sign_channel_tx(
+		&self, tx: TransactionView, staking_ckb: u64, bet_ckb: u64, max_nfts_count: u8, nfts: Vec<[u8; 20]>
+	) -> Result<TransactionView, String> {
+		self.with_privkey(|privkey| {
+			channel::sign_channel_tx(tx, staking_ckb, bet_ckb, max_nfts_count, nfts, privkey).map_err(|err| err.to_string())
+		})
+	}
+
+	fn sign_round(&self, script_hash: Byte32, signed_rounds: Vec<(Round, Signature)>, round: Round) -> Result<Signature, String> {
+		self.with_privkey(|privkey| {
+			channel::sign_channel_round(script_hash, signed_rounds, round, privkey).map_err(|err| err.to_string())
+		})
+	}
+}
+
+// sign all inputs locked by signer's sighash lock_script in the way of secp256k1_blake160_sighash_all, only the
+// message is passed to signer so that any kind of signer can share it
+pub fn sign_sighash_tx<S: Signer + ?Sized>(tx: TransactionView, signer: &S) -> Result<TransactionView, String> {
+	let user_lock = sighash_script(&signer.pubhash().to_vec());
+	let mut group = vec![];
+	for (i, input) in tx.inputs().into_iter().enumerate() {
+		let (output, _) = rpc::get_cell(&input.previous_output())?;
+		if output.lock() == user_lock {
+			group.push(i);
+		}
+	}
+	if group.is_empty() {
+		return Ok(tx);
+	}
+	let mut witnesses = tx.witnesses().into_iter().map(|witness| witness.raw_data()).collect::<Vec<_>>();
+	while witnesses.len() < tx.inputs().len() {
+		witnesses.push(Bytes::new());
+	}
+	let first_witness = if witnesses[group[0]].is_empty() {
+		WitnessArgs::default()
+	} else {
+		WitnessArgs::from_slice(&witnesses[group[0]]).map_err(|err| err.to_string())?
+	};
+	let zero_lock = Bytes::from(vec![0u8; 65]);
+	let first_witness = first_witness
+		.as_builder()
+		.lock(BytesOpt::new_builder().set(Some(zero_lock.pack())).build())
+		.build();
+	let mut blake2b = new_blake2b();
+	let mut message = [0u8; 32];
+	blake2b.update(tx.hash().as_slice());
+	blake2b.update(&(first_witness.as_bytes().len() as u64).to_le_bytes());
+	blake2b.update(&first_witness.as_bytes());
+	for &i in &group[1..] {
+		blake2b.update(&(witnesses[i].len() as u64).to_le_bytes());
+		blake2b.update(&witnesses[i]);
+	}
+	for witness in &witnesses[tx.inputs().len()..] {
+		blake2b.update(&(witness.len() as u64).to_le_bytes());
+		blake2b.update(witness);
+	}
+	blake2b.finalize(&mut message);
+	let signature = signer.sign_message(&H256::from(message))?;
+	witnesses[group[0]] = first_witness
+		.as_builder()
+		.lock(BytesOpt::new_builder().set(Some(Bytes::from(signature.serialize()).pack())).build())
+		.build()
+		.as_bytes();
+	Ok(tx
+		.as_advanced_builder()
+		.set_witnesses(witnesses.into_iter().map(|witness| witness.pack()).collect())
+		.build())
+}
+
+// remove the signature of inputs locked by pubhash's sighash lock_script, which kabletop-ckb-sdk puts there with
+// the configured key, so that the transaction can be kept or changed before the real signer signs it
+pub fn unsign_sighash_tx(tx: TransactionView, pubhash: &[u8; 20]) -> Result<TransactionView, String> {
+	let user_lock = sighash_script(&pubhash.to_vec());
+	let mut first = None;
+	for (i, input) in tx.inputs().into_iter().enumerate() {
+		let (output, _) = rpc::get_cell(&input.previous_output())?;
+		if output.lock() == user_lock {
+			first = Some(i);
+			break
+		}
+	}
+	let witness = match first.and_then(|i| tx.witnesses().get(i).map(|witness| (i, witness.raw_data()))) {
+		Some((i, witness)) if !witness.is_empty() => (i, witness),
+		_                                         => return Ok(tx)
+	};
+	let witness_args = WitnessArgs::from_slice(&witness.1)
+		.map_err(|err| err.to_string())?
+		.as_builder()
+		.lock(BytesOpt::default())
+		.build();
+	let unsigned = if witness_args.as_slice() == WitnessArgs::default().as_slice() {
+		Bytes::new()
+	} else {
+		witness_args.as_bytes()
+	};
+	let witnesses = tx
+		.witnesses()
+		.into_iter()
+		.enumerate()
+		.map(|(i, value)| if i == witness.0 { unsigned.pack() } else { value })
+		.collect::<Vec<_>>();
+	Ok(tx
+		.as_advanced_builder()
+		.set_witnesses(witnesses)
+		.build())
+}
\ No newline at end of file
@@ -0,0 +1,265 @@
+use super::{
+	rpc, fee::{
+		transaction_fee, signed_required_fee, DEFAULT_FEE_RATE
+	}, trade::{
+		sorted_nfts, build_nft_placeholder
+	}, nft::{
+		nft_type_script, nft_output, MIN_CHANGE_CAPACITY, FEE_RESERVE
+	}
+};
+use crate::account::{
+	active_pubhash, active_signer
+};
+use kabletop_ckb_sdk::ckb::transaction::helper::{
+	sighash_script, blake160_to_byte20
+};
+use ckb_types::{
+	bytes::Bytes, core::TransactionView, prelude::*, packed::{
+		CellInput, CellOutput
+	}
+};
+use serde::{
+	Deserialize, Serialize
+};
+use std::{
+	collections::HashMap, iter::repeat
+};
+
+// proposer gives its nfts for the nfts of partner, which are shown to partner before accepting
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SwapProposal {
+	pub proposer:  [u8; 20],
+	pub gives:     Vec<String>,
+	pub takes:     Vec<String>,
+	// which proposal player is answering, it's only known by partner
+	#[serde(default)]
+	pub answer_id: u64
+}
+
+fn count_nfts(counts: &mut HashMap<String, i64>, data: &[u8], delta: i64) {
+	for nft in data.chunks_exact(20) {
+		*counts.entry(hex::encode(nft)).or_insert(0) += delta;
+	}
+}
+
+fn into_nfts(nfts: &[String]) -> Result<Vec<[u8; 20]>, String> {
+	nfts
+		.iter()
+		.map(|nft| blake160_to_byte20(nft.as_str()).map_err(|err| err.to_string()))
+		.collect()
+}
+
+fn into_nft_data(nfts: &[String]) -> Result<Bytes, String> {
+	Ok(Bytes::from(into_nfts(nfts)?.concat()))
+}
+
+// the nft cell which partner creates for proposer, it holds what proposer takes with exactly the capacity it occupies
+fn proposer_output(proposal: &SwapProposal) -> Result<(CellOutput, Bytes), String> {
+	nft_output(&proposal.proposer, &into_nfts(&proposal.takes)?)
+}
+
+// make sure cells of owner in swap transaction give and take exactly the agreed nfts, and that the ckb of owner
+// changes no more than the real capacity of placeholder against the nft cell of proposer, apart from its own fee
+pub fn check_swap(tx: &TransactionView, nft_index: usize, owner: &[u8; 20], proposal: &SwapProposal) -> Result<(), String> {
+	let is_proposer = owner == &proposal.proposer;
+	let (gives, takes) = if is_proposer {
+		(&proposal.gives, &proposal.takes)
+	} else {
+		(&proposal.takes, &proposal.gives)
+	};
+	let placeholder_capacity: u64 = tx
+		.outputs()
+		.get(nft_index)
+		.ok_or(String::from("swap transaction has no placeholder"))?
+		.capacity()
+		.unpack();
+	let proposer_capacity: u64 = proposer_output(proposal)?.0.capacity().unpack();
+	let lock = sighash_script(&owner.to_vec());
+	let nft_type = nft_type_script();
+	let mut counts = HashMap::new();
+	let mut spent = 0u64;
+	for input in tx.inputs() {
+		let (output, data) = rpc::get_cell(&input.previous_output())?;
+		if output.lock() != lock {
+			continue
+		}
+		match output.type_().to_opt() {
+			Some(type_script) if type_script == nft_type => count_nfts(&mut counts, &data, 1),
+			None if data.is_empty()                      => (),
+			_                                            => return Err(String::from("swap transaction spends cells which are neither plain nor nft"))
+		}
+		spent += Unpack::<u64>::unpack(&output.capacity());
+	}
+	let mut received = 0u64;
+	for (output, data) in tx.outputs_with_data_iter() {
+		if output.lock() != lock {
+			continue
+		}
+		match output.type_().to_opt() {
+			Some(type_script) if type_script == nft_type => count_nfts(&mut counts, &data, -1),
+			None if data.is_empty()                      => (),
+			_                                            => return Err(String::from("swap transaction creates cells which are neither plain nor nft"))
+		}
+		received += Unpack::<u64>::unpack(&output.capacity());
+	}
+
+	// nfts left with positive count are given away, and negative ones are taken in
+	let mut given = vec![];
+	let mut taken = vec![];
+	for (nft, count) in counts {
+		if count > 0 {
+			given.extend(repeat(nft).take(count as usize));
+		} else if count < 0 {
+			taken.extend(repeat(nft).take(-count as usize));
+		}
+	}
+	given.sort();
+	taken.sort();
+	let mut gives = gives.to_vec();
+	let mut takes = takes.to_vec();
+	gives.sort();
+	takes.sort();
+	if given != gives || taken != takes {
+		return Err(String::from("swap transaction doesn't move exactly the agreed nfts"));
+	}
+	// proposer gives the placeholder away for its own nft cell, and partner does the opposite
+	let moved = proposer_capacity as i128 - placeholder_capacity as i128;
+	let expected = if is_proposer { moved } else { -moved } - FEE_RESERVE as i128;
+	let changed = received as i128 - spent as i128;
+	if changed < expected {
+		return Err(format!("swap transaction costs {} shannons more than agreed", expected - changed));
+	}
+	Ok(())
+}
+
+// proposer transfers its nfts to itself as a placeholder whose lock is replaced by partner, the transaction is left
+// unsigned until partner completes it
+pub fn build_swap_proposal(gives: &[String], takes: &[String]) -> Result<(TransactionView, usize), String> {
+	if gives.is_empty() || takes.is_empty() {
+		return Err(String::from("both sides of swap must give at least one nft"));
+	}
+	build_nft_placeholder(gives)
+}
+
+// partner takes the placeholder output, spends its nft cells to pay the nfts proposer takes, sends the rest back
+// to itself and pays its fee from plain cells, then signs its inputs once the whole transaction checks out
+pub fn complete_swap_proposal(tx: TransactionView, nft_index: usize, proposal: &SwapProposal) -> Result<TransactionView, String> {
+	let partner = active_pubhash();
+	let partner_lock = sighash_script(&partner.to_vec());
+	let nft_type = nft_type_script();
+	if tx.outputs().len() != tx.outputs_data().len() || nft_index >= tx.outputs().len() {
+		return Err(String::from("swap transaction is malformed"));
+	}
+	let placeholder = tx.outputs().get(nft_index).unwrap();
+	let mut gives = proposal.gives.clone();
+	gives.sort();
+	if placeholder.type_().to_opt() != Some(nft_type.clone())
+		|| sorted_nfts(&tx.outputs_data().get(nft_index).unwrap().raw_data()) != gives {
+		return Err(String::from("nft output of swap transaction mismatches nfts of proposer"));
+	}
+	for input in tx.inputs() {
+		if rpc::get_cell(&input.previous_output())?.0.lock() == partner_lock {
+			return Err(String::from("swap transaction spends cells of partner"));
+		}
+	}
+
+	// spend nft cells of partner until all nfts proposer takes are collected, the others of them are kept by partner
+	let mut needed = HashMap::new();
+	count_nfts(&mut needed, &into_nft_data(&proposal.takes)?, 1);
+	let mut inputs = vec![];
+	let mut capacity = 0u64;
+	let mut kept = vec![];
+	for (out_point, output, data) in rpc::get_live_cells(&partner_lock, Some(&nft_type), usize::MAX)? {
+		if needed.is_empty() {
+			break
+		}
+		let nfts = data.chunks_exact(20).map(hex::encode).collect::<Vec<_>>();
+		if !nfts.iter().any(|nft| needed.contains_key(nft)) {
+			continue
+		}
+		for nft in nfts {
+			match needed.get_mut(&nft) {
+				Some(count) => {
+					*count -= 1;
+					if *count == 0 {
+						needed.remove(&nft);
+					}
+				},
+				None => kept.push(nft)
+			}
+		}
+		inputs.push(CellInput::new(out_point, 0));
+		capacity += Unpack::<u64>::unpack(&output.capacity());
+	}
+	if !needed.is_empty() {
+		return Err(format!("partner doesn't own nfts {:?}", needed.keys().collect::<Vec<_>>()));
+	}
+
+	// partner takes the placeholder with its real capacity, and pays proposer an nft cell of the exact occupied capacity
+	let (proposer_output, proposer_data) = proposer_output(proposal)?;
+	let proposer_capacity: u64 = proposer_output.capacity().unpack();
+	let mut outputs = tx
+		.outputs()
+		.into_iter()
+		.enumerate()
+		.map(|(i, output)| {
+			if i == nft_index {
+				output.as_builder().lock(partner_lock.clone()).build()
+			} else {
+				output
+			}
+		})
+		.collect::<Vec<_>>();
+	let mut outputs_data = tx.outputs_data().into_iter().collect::<Vec<_>>();
+	outputs.push(proposer_output);
+	outputs_data.push(proposer_data.pack());
+	let mut target = proposer_capacity + MIN_CHANGE_CAPACITY + FEE_RESERVE;
+	if !kept.is_empty() {
+		let (kept_output, kept_data) = nft_output(&partner, &into_nfts(&kept)?)?;
+		target += Unpack::<u64>::unpack(&kept_output.capacity());
+		outputs.push(kept_output);
+		outputs_data.push(kept_data.pack());
+	}
+
+	// pay the rest and the fee of partner's part from its plain cells
+	if capacity < target {
+		for (out_point, output, data) in rpc::get_live_cells(&partner_lock, None, usize::MAX)? {
+			if output.type_().is_some() || !data.is_empty() {
+				continue
+			}
+			inputs.push(CellInput::new(out_point, 0));
+			capacity += Unpack::<u64>::unpack(&output.capacity());
+			if capacity >= target {
+				break
+			}
+		}
+	}
+	if capacity < target {
+		return Err(format!("swapping needs {} shannons, but only {} is spendable", target, capacity));
+	}
+	let change = capacity - target + MIN_CHANGE_CAPACITY + FEE_RESERVE;
+	let complete = |change_capacity: u64| tx
+		.as_advanced_builder()
+		.set_outputs(outputs.clone())
+		.set_outputs_data(outputs_data.clone())
+		.inputs(inputs.clone())
+		.output(CellOutput::new_builder()
+			.lock(partner_lock.clone())
+			.capacity(change_capacity.pack())
+			.build())
+		.output_data(Bytes::new().pack())
+		.build();
+
+	// the fee of proposer is kept, and partner pays for the size grown by its part
+	let completed = complete(change);
+	let extra_fee = signed_required_fee(&completed, DEFAULT_FEE_RATE)?.saturating_sub(transaction_fee(&completed)?);
+	let completed = complete(change - extra_fee);
+	check_swap(&completed, nft_index, &partner, proposal)?;
+	active_signer().sign_tx(completed)
+}
+
+// proposer makes sure the transaction completed by partner moves exactly the agreed nfts and ckb, then signs
+pub fn sign_completed_swap(completed: TransactionView, nft_index: usize, proposal: &SwapProposal) -> Result<TransactionView, String> {
+	check_swap(&completed, nft_index, &active_pubhash(), proposal)?;
+	active_signer().sign_tx(completed)
+}
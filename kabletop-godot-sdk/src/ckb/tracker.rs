@@ -0,0 +1,262 @@
+use super::rpc::{
+	self, ChainStatus
+};
+use ckb_types::H256;
+use serde::{
+	Deserialize, Serialize
+};
+use std::{
+	thread, collections::HashMap, time::Duration, sync::{
+		Mutex, Condvar, Arc
+	}
+};
+
+const TRACKER_DIR: &str = "db/tracker";
+const MIN_INTERVAL: u64 = 2;
+const MAX_INTERVAL: u64 = 60;
+const UNKNOWN_LIMIT: u32 = 12;
+
+// the stages one submitted transaction goes through
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum TxStatus {
+	Pending,
+	Proposed,
+	Committed,
+	Rejected(String)
+}
+
+impl TxStatus {
+	pub fn name(&self) -> &str {
+		match self {
+			TxStatus::Pending     => "pending",
+			TxStatus::Proposed    => "proposed",
+			TxStatus::Committed   => "committed",
+			TxStatus::Rejected(_) => "rejected"
+		}
+	}
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct TrackedTx {
+	status:        TxStatus,
+	confirmations: u64,
+	#[serde(skip)]
+	unknown_count: u32,
+	#[serde(skip)]
+	interval:      u64,
+	#[serde(skip)]
+	countdown:     u64
+}
+
+impl TrackedTx {
+	fn new() -> Self {
+		TrackedTx {
+			status:        TxStatus::Pending,
+			confirmations: 0,
+			unknown_count: 0,
+			interval:      MIN_INTERVAL,
+			countdown:     0
+		}
+	}
+
+	fn is_final(&self, depth: u64) -> bool {
+		match self.status {
+			TxStatus::Committed   => self.confirmations >= depth,
+			TxStatus::Rejected(_) => true,
+			_                     => false
+		}
+	}
+}
+
+struct Tracker {
+	transactions: HashMap<H256, TrackedTx>,
+	depth:        u64,
+	running:      bool,
+	listener:     Option<Arc<dyn Fn(&H256, &TxStatus, u64) + Send + Sync + 'static>>
+}
+
+lazy_static! {
+	static ref TRACKER: Mutex<Tracker> = Mutex::new(Tracker {
+		transactions: HashMap::new(),
+		depth:        1,
+		running:      false,
+		listener:     None
+	});
+	static ref NOTIFIER: Condvar = Condvar::new();
+}
+
+// set how many blocks should be built upon the committed block before regarding transaction as committed
+pub fn set_confirmation_depth(depth: u64) {
+	TRACKER.lock().unwrap().depth = std::cmp::max(depth, 1);
+}
+
+pub fn confirmation_depth() -> u64 {
+	TRACKER.lock().unwrap().depth
+}
+
+// set a function to receive every status change of tracked transactions
+pub fn set_tx_status_listener<F>(listener: F)
+where
+	F: Fn(&H256, &TxStatus, u64) + Send + Sync + 'static
+{
+	TRACKER.lock().unwrap().listener = Some(Arc::new(listener));
+}
+
+// put transaction into tracker and start polling its status in background
+pub fn track_transaction(hash: H256) {
+	let mut tracker = TRACKER.lock().unwrap();
+	if !tracker.transactions.contains_key(&hash) {
+		tracker.transactions.insert(hash, TrackedTx::new());
+		persist(&tracker);
+	}
+	if !tracker.running {
+		tracker.running = true;
+		thread::spawn(poll_transactions);
+	}
+}
+
+// block current thread until the tracked transaction is committed or rejected, which is only for channel protocol
+// whose next step needs the transaction on chain, a pending transaction is never given up since it's still tracked
+pub fn wait_transaction(hash: &H256) -> Result<H256, String> {
+	let mut tracker = TRACKER.lock().unwrap();
+	loop {
+		match tracker.transactions.get(hash) {
+			Some(tx) if tx.is_final(tracker.depth) => {
+				return match &tx.status {
+					TxStatus::Rejected(reason) => Err(format!("transaction ({}) rejected: {}", hash, reason)),
+					_                          => Ok(hash.clone())
+				}
+			},
+			Some(_) => tracker = NOTIFIER.wait(tracker).unwrap(),
+			None    => return Err(format!("transaction ({}) isn't tracked", hash))
+		}
+	}
+}
+
+// tracked transactions which are still in tx-pool, no matter they are pending or proposed
+pub fn uncommitted_transactions() -> Vec<H256> {
+	TRACKER
+		.lock()
+		.unwrap()
+		.transactions
+		.iter()
+		.filter(|(_, tx)| tx.status == TxStatus::Pending || tx.status == TxStatus::Proposed)
+		.map(|(hash, _)| hash.clone())
+		.collect()
+}
+
+// restore unfinished transactions which are persisted before last exit and keep tracking them
+pub fn resume_transactions() -> Result<usize, String> {
+	let path = format!("{}/transactions.json", TRACKER_DIR);
+	let transactions: HashMap<H256, TrackedTx> = match std::fs::read_to_string(path.clone()) {
+		Ok(content) => serde_json::from_str(content.as_str()).map_err(|err| format!("{} => {}", err, path))?,
+		Err(_)      => return Ok(0)
+	};
+	let count = transactions.len();
+	for (hash, tx) in transactions {
+		TRACKER.lock().unwrap().transactions.insert(hash.clone(), TrackedTx {
+			interval: MIN_INTERVAL, ..tx
+		});
+		track_transaction(hash);
+	}
+	Ok(count)
+}
+
+fn persist(tracker: &Tracker) {
+	let unfinished = tracker
+		.transactions
+		.iter()
+		.filter(|(_, tx)| !tx.is_final(tracker.depth))
+		.collect::<HashMap<_, _>>();
+	let result = std::fs::create_dir_all(TRACKER_DIR)
+		.and_then(|_| {
+			let content = serde_json::to_string_pretty(&unfinished).unwrap_or_default();
+			std::fs::write(format!("{}/transactions.json", TRACKER_DIR), content)
+		});
+	if let Err(err) = result {
+		println!("persist tracked transactions error: {}", err);
+	}
+}
+
+// query the next status of transaction, the waiting interval will be doubled if nothing changed
+fn poll_transaction(hash: &H256, tx: &mut TrackedTx, depth: u64, tipnumber: u64) -> bool {
+	let (status, confirmations) = match rpc::get_transaction_status(hash) {
+		Ok(ChainStatus::Pending)           => (TxStatus::Pending, 0),
+		Ok(ChainStatus::Proposed)          => (TxStatus::Proposed, 0),
+		Ok(ChainStatus::Committed(number)) => (TxStatus::Committed, std::cmp::max(tipnumber + 1, number) - number),
+		Ok(ChainStatus::Unknown)           => {
+			tx.unknown_count += 1;
+			if tx.unknown_count < UNKNOWN_LIMIT {
+				(tx.status.clone(), tx.confirmations)
+			} else {
+				(TxStatus::Rejected(String::from("transaction is dropped by ckb node")), 0)
+			}
+		},
+		Err(err) => {
+			println!("poll transaction ({}) error: {}", hash, err);
+			(tx.status.clone(), tx.confirmations)
+		}
+	};
+	let changed = status != tx.status || std::cmp::min(confirmations, depth) != std::cmp::min(tx.confirmations, depth);
+	if changed {
+		tx.status = status;
+		tx.confirmations = confirmations;
+		tx.interval = MIN_INTERVAL;
+	} else {
+		tx.interval = std::cmp::min(tx.interval * 2, MAX_INTERVAL);
+	}
+	tx.countdown = tx.interval;
+	changed
+}
+
+fn poll_transactions() {
+	loop {
+		let (pollings, depth) = {
+			let mut tracker = TRACKER.lock().unwrap();
+			let depth = tracker.depth;
+			if tracker.transactions.values().all(|tx| tx.is_final(depth)) {
+				tracker.running = false;
+				return
+			}
+			let pollings = tracker
+				.transactions
+				.iter_mut()
+				.filter(|(_, tx)| !tx.is_final(depth))
+				.filter_map(|(hash, tx)| {
+					if tx.countdown == 0 {
+						Some((hash.clone(), tx.clone()))
+					} else {
+						tx.countdown -= 1;
+						None
+					}
+				})
+				.collect::<Vec<_>>();
+			(pollings, depth)
+		};
+		if !pollings.is_empty() {
+			let tipnumber = rpc::get_tip_block_number().unwrap_or(0);
+			let mut changes = vec![];
+			for (hash, mut tx) in pollings {
+				if poll_transaction(&hash, &mut tx, depth, tipnumber) {
+					changes.push((hash.clone(), tx.status.clone(), tx.confirmations));
+				}
+				TRACKER.lock().unwrap().transactions.insert(hash, tx);
+			}
+			if !changes.is_empty() {
+				// listener may call back into tracker, so it runs after the lock is released
+				let listener = {
+					let tracker = TRACKER.lock().unwrap();
+					persist(&tracker);
+					NOTIFIER.notify_all();
+					tracker.listener.clone()
+				};
+				if let Some(listener) = listener {
+					for (hash, status, confirmations) in &changes {
+						listener(hash, status, *confirmations);
+					}
+				}
+			}
+		}
+		thread::sleep(Duration::from_secs(1));
+	}
+}
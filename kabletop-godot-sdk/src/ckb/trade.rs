@@ -0,0 +1,248 @@
+use super::{
+	rpc, fee::{
+		transaction_fee, signed_required_fee, DEFAULT_FEE_RATE
+	}, nft::{
+		nft_type_script, nft_output, owner_cells, collect_nfts, build_user_transaction, MIN_CHANGE_CAPACITY, FEE_RESERVE
+	}
+};
+use crate::account::{
+	active_pubhash, active_signer
+};
+use kabletop_ckb_sdk::ckb::transaction::helper::{
+	sighash_script, blake160_to_byte20
+};
+use ckb_types::{
+	H256, bytes::Bytes, core::{
+		TransactionView, Capacity
+	}, prelude::*, packed::{
+		CellInput, CellOutput
+	}
+};
+use serde::{
+	Deserialize, Serialize
+};
+use futures::channel::oneshot;
+use std::{
+	sync::Mutex, thread, time::Duration, collections::{
+		HashMap, VecDeque
+	}
+};
+
+// how long buyer waits for player to answer an offer
+const ANSWER_TIMEOUT: u64 = 60;
+
+// how many completed trades are remembered for opposite to submit
+const MAX_COMPLETED_TRADES: usize = 32;
+
+// nfts offered by seller at the price in shannons, which is shown to buyer before accepting
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TradeOffer {
+	pub seller:    [u8; 20],
+	pub nfts:      Vec<String>,
+	pub price:     u64,
+	// which offer player is answering, it's only known by buyer
+	#[serde(default)]
+	pub answer_id: u64
+}
+
+lazy_static! {
+	// offers and proposals waiting for player, each of them is answered through its own channel
+	static ref ANSWERS: Mutex<(u64, HashMap<u64, oneshot::Sender<bool>>)> = Mutex::new((0, HashMap::new()));
+	static ref COMPLETED: Mutex<VecDeque<H256>> = Mutex::new(VecDeque::new());
+}
+
+// remember the hash of trade or swap transaction which user completed and signed, the oldest one is forgotten if full
+pub fn remember_completed_trade(hash: H256) {
+	let mut completed = COMPLETED.lock().unwrap();
+	if completed.len() >= MAX_COMPLETED_TRADES {
+		completed.pop_front();
+	}
+	completed.push_back(hash);
+}
+
+// only transactions completed by user are submitted for opposite, witnesses are out of tx hash so they're signed ones
+pub fn is_completed_trade(hash: &H256) -> bool {
+	COMPLETED.lock().unwrap().contains(hash)
+}
+
+pub(super) fn sorted_nfts(data: &[u8]) -> Vec<String> {
+	let mut nfts = data.chunks_exact(20).map(hex::encode).collect::<Vec<_>>();
+	nfts.sort();
+	nfts
+}
+
+// accept or decline the trade offer or swap proposal which is waiting for player
+pub fn answer_trade_offer(answer_id: u64, accept: bool) -> Result<(), String> {
+	let sender = ANSWERS
+		.lock()
+		.unwrap()
+		.1
+		.remove(&answer_id)
+		.ok_or(format!("offer {} isn't waiting for answer", answer_id))?;
+	let _ = sender.send(accept);
+	Ok(())
+}
+
+// open an answer channel for a new offer, which is declined by itself if player doesn't answer in time
+pub fn expect_trade_answer() -> (u64, oneshot::Receiver<bool>) {
+	let (sender, receiver) = oneshot::channel();
+	let answer_id = {
+		let mut answers = ANSWERS.lock().unwrap();
+		answers.0 += 1;
+		let answer_id = answers.0;
+		answers.1.insert(answer_id, sender);
+		answer_id
+	};
+	thread::spawn(move || {
+		thread::sleep(Duration::from_secs(ANSWER_TIMEOUT));
+		let _ = answer_trade_offer(answer_id, false);
+	});
+	(answer_id, receiver)
+}
+
+// transfer nfts to user itself as a placeholder whose lock is replaced by opposite, and return the index of it
+pub(super) fn build_nft_placeholder(nfts: &[String]) -> Result<(TransactionView, usize), String> {
+	let user = active_pubhash();
+	let nft_bytes = nfts
+		.iter()
+		.map(|nft| blake160_to_byte20(nft.as_str()).map_err(|err| err.to_string()))
+		.collect::<Result<Vec<_>, _>>()?;
+	let (nft_cells, ckb_cells) = owner_cells(&user)?;
+	let (inputs, left) = collect_nfts(&nft_cells, &nft_bytes)?;
+	let mut outputs = vec![nft_output(&user, &nft_bytes)?];
+	if !left.is_empty() {
+		outputs.push(nft_output(&user, &left)?);
+	}
+	let tx = build_user_transaction(inputs, outputs, &ckb_cells, DEFAULT_FEE_RATE)?;
+	Ok((tx, 0))
+}
+
+// seller transfers the offered nfts to itself as a placeholder whose lock is replaced by buyer, and appends the
+// payment output of price, the transaction is left unsigned until buyer completes it
+pub fn build_trade_offer(nfts: &[String], price: u64) -> Result<(TransactionView, usize), String> {
+	let seller_lock = sighash_script(&active_pubhash().to_vec());
+	let payment = CellOutput::new_builder()
+		.lock(seller_lock)
+		.build();
+	let occupied = payment
+		.occupied_capacity(Capacity::zero())
+		.map_err(|err| err.to_string())?
+		.as_u64();
+	if price < occupied {
+		return Err(format!("price {} is lower than {} which the payment cell occupies", price, occupied));
+	}
+	let (tx, nft_index) = build_nft_placeholder(nfts)?;
+	let tx = tx
+		.as_advanced_builder()
+		.output(payment.as_builder().capacity(price.pack()).build())
+		.output_data(Bytes::new().pack())
+		.set_witnesses(vec![])
+		.build();
+	Ok((tx, nft_index))
+}
+
+// buyer checks the offer transaction, takes the nft output, pays price from its plain cells and signs its inputs
+pub fn complete_trade_offer(tx: TransactionView, nft_index: usize, offer: &TradeOffer) -> Result<TransactionView, String> {
+	let buyer_lock = sighash_script(&active_pubhash().to_vec());
+	let seller_lock = sighash_script(&offer.seller.to_vec());
+	if tx.outputs().len() != tx.outputs_data().len() || nft_index + 1 >= tx.outputs().len() {
+		return Err(String::from("offer transaction is malformed"));
+	}
+	let nft_output = tx.outputs().get(nft_index).unwrap();
+	let mut offered = offer.nfts.clone();
+	offered.sort();
+	if nft_output.type_().to_opt() != Some(nft_type_script())
+		|| sorted_nfts(&tx.outputs_data().get(nft_index).unwrap().raw_data()) != offered {
+		return Err(String::from("nft output of offer transaction mismatches offered nfts"));
+	}
+	let payment_index = tx.outputs().len() - 1;
+	let payment = tx.outputs().get(payment_index).unwrap();
+	if payment.lock() != seller_lock
+		|| payment.type_().is_some()
+		|| !tx.outputs_data().get(payment_index).unwrap().raw_data().is_empty()
+		|| Unpack::<u64>::unpack(&payment.capacity()) != offer.price {
+		return Err(String::from("payment output of offer transaction mismatches price"));
+	}
+	for input in tx.inputs() {
+		if rpc::get_cell(&input.previous_output())?.0.lock() == buyer_lock {
+			return Err(String::from("offer transaction spends cells of buyer"));
+		}
+	}
+
+	// pay price and the fee of extra inputs and outputs from buyer's plain cells
+	let target = offer.price + MIN_CHANGE_CAPACITY + FEE_RESERVE;
+	let mut inputs = vec![];
+	let mut capacity = 0u64;
+	for (out_point, output, data) in rpc::get_live_cells(&buyer_lock, None, usize::MAX)? {
+		if output.type_().is_some() || !data.is_empty() {
+			continue
+		}
+		inputs.push(CellInput::new(out_point, 0));
+		capacity += Unpack::<u64>::unpack(&output.capacity());
+		if capacity >= target {
+			break
+		}
+	}
+	if capacity < target {
+		return Err(format!("buying needs {} shannons, but only {} is spendable", target, capacity));
+	}
+	let outputs = tx
+		.outputs()
+		.into_iter()
+		.enumerate()
+		.map(|(i, output)| {
+			if i == nft_index {
+				output.as_builder().lock(buyer_lock.clone()).build()
+			} else {
+				output
+			}
+		})
+		.collect::<Vec<_>>();
+	let complete = |change_capacity: u64| tx
+		.as_advanced_builder()
+		.set_outputs(outputs.clone())
+		.inputs(inputs.clone())
+		.output(CellOutput::new_builder()
+			.lock(buyer_lock.clone())
+			.capacity(change_capacity.pack())
+			.build())
+		.output_data(Bytes::new().pack())
+		.build();
+
+	// the fee of seller is kept, and buyer pays for the size grown by its part
+	let completed = complete(capacity - offer.price);
+	let extra_fee = signed_required_fee(&completed, DEFAULT_FEE_RATE)?.saturating_sub(transaction_fee(&completed)?);
+	active_signer().sign_tx(complete(capacity - offer.price - extra_fee))
+}
+
+// seller makes sure buyer only changed the lock of nft output and appended its own inputs and outputs, then signs
+pub fn sign_completed_trade(offer: &TransactionView, nft_index: usize, completed: TransactionView) -> Result<TransactionView, String> {
+	let seller_lock = sighash_script(&active_pubhash().to_vec());
+	let offer_inputs = offer.inputs().into_iter().map(|input| input.as_bytes()).collect::<Vec<_>>();
+	let completed_inputs = completed.inputs().into_iter().collect::<Vec<_>>();
+	if completed_inputs.len() < offer_inputs.len()
+		|| completed_inputs.iter().zip(offer_inputs.iter()).any(|(input, offer_input)| &input.as_bytes() != offer_input) {
+		return Err(String::from("inputs of offer are changed by buyer"));
+	}
+	for input in &completed_inputs[offer_inputs.len()..] {
+		if rpc::get_cell(&input.previous_output())?.0.lock() == seller_lock {
+			return Err(String::from("buyer spends cells of seller"));
+		}
+	}
+	if completed.outputs().len() < offer.outputs().len() || completed.cell_deps().as_bytes() != offer.cell_deps().as_bytes() {
+		return Err(String::from("outputs or cell deps of offer are changed by buyer"));
+	}
+	for (i, (output, data)) in offer.outputs_with_data_iter().enumerate() {
+		let (completed_output, completed_data) = (completed.outputs().get(i).unwrap(), completed.outputs_data().get(i).unwrap());
+		let same = if i == nft_index {
+			completed_output.type_().as_bytes() == output.type_().as_bytes()
+				&& completed_output.capacity().as_bytes() == output.capacity().as_bytes()
+		} else {
+			completed_output.as_bytes() == output.as_bytes()
+		};
+		if !same || completed_data.raw_data() != data {
+			return Err(format!("output {} of offer is changed by buyer", i));
+		}
+	}
+	active_signer().sign_tx(completed)
+}
\ No newline at end of file
@@ -0,0 +1,558 @@
+use kabletop_ckb_sdk::{
+	config::VARS, ckb::{
+		wallet::keystore, transaction::{
+			builder::*, helper::*, channel::protocol::{
+				Round, Challenge
+			}
+		}
+	}
+};
+use futures::{
+	executor::block_on, future::BoxFuture
+};
+use ckb_types::{
+	bytes::Bytes, prelude::*, core::TransactionView, packed::{
+		CellInput, CellOutput, Script
+	}
+};
+use std::{
+	thread, collections::HashMap, convert::TryInto
+};
+use crate::account::{
+	active_pubhash, active_signer
+};
+use super::{
+	rpc::{
+		self, ChainStatus
+	}, address::parse_recipient, fee::{
+		apply_fee_rate, transaction_fee, DEFAULT_FEE_RATE
+	}, tracker::{
+		track_transaction
+	}, balance::record_closed_channel, signer::unsign_sighash_tx, nft::{
+		build_tx_move_nfts, owner_cells, MIN_CHANGE_CAPACITY, FEE_RESERVE
+	}
+};
+use molecule::prelude::Entity;
+use ckb_crypto::secp::Signature;
+pub use ckb_types::H256;
+
+// nft cells fetched from indexer in one request
+const NFT_PAGE_SIZE: u32 = 50;
+
+// get user owned nfts by their lock_script (from user_pkhash) and type_script (from composer_pkhash)
+pub fn owned_nfts() -> Result<HashMap<String, u32>, String> {
+	owned_nfts_with_progress(|_, _| {})
+}
+
+// page through all nft cells of user, progress receives nfts decoded from every page and the count of cells fetched so far
+pub fn owned_nfts_with_progress<F>(mut progress: F) -> Result<HashMap<String, u32>, String>
+where
+	F: FnMut(&HashMap<String, u32>, usize)
+{
+	let lock_script = sighash_script(&active_pubhash().to_vec());
+	let type_script = {
+		let wallet = wallet_script(keystore::COMPOSER_PUBHASH.to_vec());
+		nft_script(wallet.calc_script_hash().raw_data().to_vec())
+	};
+	let mut nfts = HashMap::new();
+	let mut cells = 0;
+	let mut after = None;
+	loop {
+		let (page, cursor) = rpc::get_live_cells_page(&lock_script, Some(&type_script), NFT_PAGE_SIZE, after)?;
+		cells += page.len();
+		let mut page_nfts = HashMap::new();
+		for (_, _, data) in page {
+			for hash in data.chunks_exact(20) {
+				*page_nfts.entry(hex::encode(hash)).or_insert(0) += 1;
+			}
+		}
+		progress(&page_nfts, cells);
+		for (nft, count) in page_nfts {
+			*nfts.entry(nft).or_insert(0) += count;
+		}
+		match cursor {
+			Some(cursor) => after = Some(cursor),
+			None         => break
+		}
+	}
+	Ok(nfts)
+}
+
+// boxes are paid for in purchase mode, and then wallet stays in reveal mode until they are revealed into nfts
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WalletMode {
+	Absent,
+	Purchase,
+	Reveal
+}
+
+impl WalletMode {
+	pub fn name(&self) -> &str {
+		match self {
+			WalletMode::Absent   => "absent",
+			WalletMode::Purchase => "purchase",
+			WalletMode::Reveal   => "reveal"
+		}
+	}
+}
+
+// user wallet cell status
+//
+// the wallet cell is locked by composer's wallet script and typed by user's payment script, and its data is written
+// by build_tx_create_nft_store and build_tx_purchase_nft_package of kabletop-ckb-sdk:
+//
+//   [0]    pending box count (u8), zero in purchase mode
+//   [1..9] price per box in shannons (u64 LE), which is set by composer
+//
+// pending boxes are revealed by the hash of a block after the one committing the purchase, so the reveal
+// transaction can only be built from the next block on
+#[derive(Clone, PartialEq, Debug)]
+pub struct WalletStatus {
+	pub mode:          WalletMode,
+	pub pending_boxes: u8,
+	pub box_price:     u64,
+	// the first block whose hash can reveal pending boxes
+	pub reveal_block:  Option<u64>,
+	// capacity of all wallet cells of composer, which is paid by purchased boxes
+	pub pool_capacity: u64
+}
+
+// get user wallet cell status, including existence and payment or reveal status
+pub fn wallet_status() -> Result<WalletStatus, String> {
+	let wallet_script = wallet_script(keystore::COMPOSER_PUBHASH.to_vec());
+	let user_payment_script = payment_script(active_pubhash().to_vec());
+	let pool_capacity = rpc::get_capacity(&wallet_script)?;
+	let (out_point, _, data) = match rpc::get_live_cells(&wallet_script, Some(&user_payment_script), 1)?.into_iter().next() {
+		Some(cell) => cell,
+		None       => return Ok(WalletStatus {
+			mode:          WalletMode::Absent,
+			pending_boxes: 0,
+			box_price:     0,
+			reveal_block:  None,
+			pool_capacity
+		})
+	};
+	let pending_boxes = data.first().cloned().unwrap_or(0);
+	let box_price = match data.get(1..9) {
+		Some(price) => u64::from_le_bytes(price.try_into().unwrap()),
+		None        => return Err(format!("wallet cell data 0x{} has no box price", hex::encode(&data)))
+	};
+	if pending_boxes == 0 {
+		return Ok(WalletStatus {
+			mode:          WalletMode::Purchase,
+			pending_boxes,
+			box_price,
+			reveal_block:  None,
+			pool_capacity
+		});
+	}
+	// the wallet cell is rewritten by purchase, so boxes can be revealed from the block next to it
+	let tx_hash: H256 = out_point.tx_hash().unpack();
+	let reveal_block = match rpc::get_transaction_status(&tx_hash)? {
+		ChainStatus::Committed(block_number) => Some(block_number + 1),
+		_                                    => None
+	};
+	Ok(WalletStatus {
+		mode: WalletMode::Reveal,
+		pending_boxes,
+		box_price,
+		reveal_block,
+		pool_capacity
+	})
+}
+
+// get user total capacity on ckb network
+pub fn online_capacity() -> Result<u64, String> {
+	rpc::get_capacity(&sighash_script(&active_pubhash().to_vec()))
+}
+
+// push transaction to ckb network through rpc handler, it succeeds once the node accepts it, and the progress
+// afterwards is only reported by tracker through tx_status events
+pub(crate) fn push_transaction(tx: TransactionView) -> Result<H256, String> {
+	let hash = rpc::send_transaction(&tx)?;
+	track_transaction(hash.clone());
+	Ok(hash)
+}
+
+// wallet operations which are built into transactions by kabletop-ckb-sdk
+#[derive(Clone)]
+pub enum WalletAction {
+	Discard(Vec<String>),
+	Transfer(Vec<String>, String),
+	TransferBatch(Vec<(String, Vec<String>)>),
+	Issue(Vec<String>),
+	Purchase(u8),
+	Reveal,
+	CreateWallet
+}
+
+fn into_byte20_nfts(nfts: &[String]) -> Result<Vec<[u8; 20]>, String> {
+	nfts
+		.iter()
+		.map(|nft| blake160_to_byte20(nft.as_str()).map_err(|e| e.to_string()))
+		.collect::<Result<Vec<_>, _>>()
+}
+
+// transaction builders of kabletop-ckb-sdk collect inputs of the configured account and sign them by its key, so
+// the signature is removed, and if another account is active, plain inputs and change of the configured account
+// are replaced by plain cells of the active one, cells of the configured account in any other form fail it
+pub(crate) fn adopt_builder_transaction(tx: TransactionView) -> Result<TransactionView, String> {
+	let configured = VARS.common.user_key.pubhash;
+	let tx = unsign_sighash_tx(tx, &configured)?;
+	let user = active_pubhash();
+	if user == configured {
+		return Ok(tx);
+	}
+	let configured_lock = sighash_script(&configured.to_vec());
+	let is_configured_plain = |output: &CellOutput, data: &[u8]| {
+		output.lock() == configured_lock && output.type_().is_none() && data.is_empty()
+	};
+	let involves_configured = |output: &CellOutput| {
+		let args_of = |script: Script| script.args().raw_data().windows(20).any(|args| args == &configured[..]);
+		args_of(output.lock()) || output.type_().to_opt().map_or(false, args_of)
+	};
+	let witnesses = tx.witnesses();
+	let mut inputs = vec![];
+	let mut input_witnesses = vec![];
+	let mut spent = 0u64;
+	for (i, input) in tx.inputs().into_iter().enumerate() {
+		let (output, data) = rpc::get_cell(&input.previous_output())?;
+		if is_configured_plain(&output, &data) {
+			spent += Unpack::<u64>::unpack(&output.capacity());
+			continue
+		}
+		if involves_configured(&output) {
+			return Err(String::from("transaction spends cells of the configured account, please switch back to it"));
+		}
+		inputs.push(input);
+		input_witnesses.push(witnesses.get(i).unwrap_or_default());
+	}
+	let mut outputs = vec![];
+	let mut outputs_data = vec![];
+	let mut returned = 0u64;
+	for (output, data) in tx.outputs_with_data_iter() {
+		if is_configured_plain(&output, &data) {
+			returned += Unpack::<u64>::unpack(&output.capacity());
+			continue
+		}
+		if involves_configured(&output) {
+			return Err(String::from("transaction creates cells for the configured account, please switch back to it"));
+		}
+		outputs.push(output);
+		outputs_data.push(data.pack());
+	}
+	let paid = spent
+		.checked_sub(returned)
+		.ok_or(String::from("transaction pays ckb to the configured account, please switch back to it"))?;
+
+	// active account pays what the configured account paid and gets the change
+	let (_, ckb_cells) = owner_cells(&user)?;
+	let target = paid + MIN_CHANGE_CAPACITY;
+	let mut capacity = 0u64;
+	for (out_point, output, _) in ckb_cells {
+		if capacity >= target {
+			break
+		}
+		capacity += Unpack::<u64>::unpack(&output.capacity());
+		inputs.push(CellInput::new(out_point, 0));
+		input_witnesses.push(Bytes::new().pack());
+	}
+	if capacity < target {
+		return Err(format!("transaction needs {} shannons, but only {} is spendable", target, capacity));
+	}
+	outputs.push(CellOutput::new_builder()
+		.lock(sighash_script(&user.to_vec()))
+		.capacity((capacity - paid).pack())
+		.build());
+	outputs_data.push(Bytes::new().pack());
+	let tx = tx
+		.as_advanced_builder()
+		.set_inputs(inputs)
+		.set_outputs(outputs)
+		.set_outputs_data(outputs_data)
+		.set_witnesses(input_witnesses.into_iter().chain(witnesses.into_iter().skip(tx.inputs().len())).collect())
+		.build();
+	apply_fee_rate(tx, DEFAULT_FEE_RATE)
+}
+
+// move transaction built by kabletop-ckb-sdk onto the active account, adjust its fee if fee rate is specified, and
+// then sign it by the active signer
+pub(crate) fn sign_builder_transaction(tx: TransactionView, fee_rate: Option<u64>) -> Result<TransactionView, String> {
+	let tx = adopt_builder_transaction(tx)?;
+	let tx = match fee_rate {
+		Some(fee_rate) => apply_fee_rate(tx, fee_rate)?,
+		None           => tx
+	};
+	active_signer().sign_tx(tx)
+}
+
+// boxes can only be purchased from a wallet in purchase mode, and user's plain cells must cover all of them and the fee
+fn check_purchase(count: u8) -> Result<(), String> {
+	if count == 0 {
+		return Err(String::from("at least one box should be purchased"));
+	}
+	let status = wallet_status()?;
+	match status.mode {
+		WalletMode::Absent   => Err(String::from("wallet cell doesn't exist, please create wallet first")),
+		WalletMode::Reveal   => Err(format!("{} purchased boxes are waiting to be revealed", status.pending_boxes)),
+		WalletMode::Purchase => {
+			let cost = status.box_price
+				.checked_mul(count as u64)
+				.ok_or(String::from("cost of boxes overflows"))?;
+			// nft and other typed cells can't pay for boxes
+			let (_, ckb_cells) = owner_cells(&active_pubhash())?;
+			let spendable: u64 = ckb_cells
+				.iter()
+				.map(|(_, output, _)| Unpack::<u64>::unpack(&output.capacity()))
+				.sum();
+			if cost.saturating_add(FEE_RESERVE) > spendable {
+				return Err(format!(
+					"{} boxes cost {} shannons plus fee, but spendable balance is only {}", count, cost, spendable
+				));
+			}
+			Ok(())
+		}
+	}
+}
+
+// kabletop-ckb-sdk keys the payment and wallet cells of boxes to the configured account, which can't be moved onto
+// another account like plain cells, and neither the wallet price nor how boxes are revealed is known out of it
+fn is_default_account() -> bool {
+	active_pubhash() == VARS.common.user_key.pubhash
+}
+
+fn check_default_account(action: &str) -> Result<(), String> {
+	if !is_default_account() {
+		return Err(format!("only the default account can {}, please switch back to it", action));
+	}
+	Ok(())
+}
+
+// build unsigned transaction of wallet action for the active account, and adjust its fee if fee rate is specified,
+// nft transfers are built locally and pay fee in default rate if no rate is specified, while wallet and boxes are
+// only handled for the default account
+pub fn build_wallet_transaction(action: WalletAction, fee_rate: Option<u64>) -> Result<TransactionView, String> {
+	let local_fee_rate = fee_rate.unwrap_or(DEFAULT_FEE_RATE);
+	let tx = match action {
+		// nft cells of another account can't be spent by the builders of kabletop-ckb-sdk, so single transfer and
+		// discard are only built locally for accounts other than the default one
+		WalletAction::Discard(nfts) if is_default_account() => {
+			let tx = block_on(build_tx_discard_nft(into_byte20_nfts(&nfts)?)).map_err(|err| err.to_string())?;
+			adopt_builder_transaction(tx)?
+		},
+		WalletAction::Discard(nfts) => build_tx_move_nfts(vec![], into_byte20_nfts(&nfts)?, local_fee_rate)?,
+		WalletAction::Transfer(nfts, to) if is_default_account() => {
+			let to = parse_recipient(to.as_str())?;
+			let tx = block_on(build_tx_transfer_nft(into_byte20_nfts(&nfts)?, to)).map_err(|err| err.to_string())?;
+			adopt_builder_transaction(tx)?
+		},
+		WalletAction::Transfer(nfts, to) => {
+			let to = parse_recipient(to.as_str())?;
+			build_tx_move_nfts(vec![(to, into_byte20_nfts(&nfts)?)], vec![], local_fee_rate)?
+		},
+		WalletAction::TransferBatch(transfers) => {
+			let transfers = transfers
+				.iter()
+				.map(|(to, nfts)| Ok((parse_recipient(to.as_str())?, into_byte20_nfts(nfts)?)))
+				.collect::<Result<Vec<_>, String>>()?;
+			build_tx_move_nfts(transfers, vec![], local_fee_rate)?
+		},
+		WalletAction::Issue(nfts) => {
+			let tx = block_on(build_tx_issue_nft(into_byte20_nfts(&nfts)?, active_pubhash())).map_err(|err| err.to_string())?;
+			adopt_builder_transaction(tx)?
+		},
+		WalletAction::Purchase(count) => {
+			check_default_account("purchase boxes")?;
+			check_purchase(count)?;
+			let tx = block_on(build_tx_purchase_nft_package(count)).map_err(|err| err.to_string())?;
+			adopt_builder_transaction(tx)?
+		},
+		WalletAction::Reveal => {
+			check_default_account("reveal boxes")?;
+			let tx = block_on(build_tx_reveal_nft_package()).map_err(|err| err.to_string())?;
+			adopt_builder_transaction(tx)?
+		},
+		WalletAction::CreateWallet => {
+			check_default_account("create wallet")?;
+			let tx = block_on(build_tx_create_nft_store()).map_err(|err| err.to_string())?;
+			adopt_builder_transaction(tx)?
+		}
+	};
+	match fee_rate {
+		Some(fee_rate) => apply_fee_rate(tx, fee_rate),
+		None           => Ok(tx)
+	}
+}
+
+// estimate the fee which wallet action will pay without submitting it
+pub fn estimate_fee(action: WalletAction, fee_rate: Option<u64>) -> Result<u64, String> {
+	let tx = build_wallet_transaction(action, fee_rate)?;
+	transaction_fee(&tx)
+}
+
+fn submit_wallet_transaction<F>(action: WalletAction, fee_rate: Option<u64>, f: F)
+where
+	F: Fn(Result<H256, String>) + Send + 'static
+{
+	thread::spawn(move || {
+		match build_wallet_transaction(action, fee_rate).and_then(|tx| active_signer().sign_tx(tx)) {
+			Ok(tx)   => f(push_transaction(tx)),
+			Err(err) => f(Err(err))
+		}
+	});
+}
+
+// remove selected nfts from user's nft cells
+pub fn discard_nfts<F>(nfts: Vec<String>, fee_rate: Option<u64>, f: F)
+where 
+	F: Fn(Result<H256, String>) + Send + 'static
+{
+	submit_wallet_transaction(WalletAction::Discard(nfts), fee_rate, f);
+}
+
+// transfer selected nfts to target address
+pub fn transfer_nfts<F>(nfts: Vec<String>, to: String, fee_rate: Option<u64>, f: F)
+where 
+	F: Fn(Result<H256, String>) + Send + 'static
+{
+	submit_wallet_transaction(WalletAction::Transfer(nfts, to), fee_rate, f);
+}
+
+// transfer selected nfts to multiple recipients in one transaction
+pub fn transfer_nfts_batch<F>(transfers: Vec<(String, Vec<String>)>, fee_rate: Option<u64>, f: F)
+where 
+	F: Fn(Result<H256, String>) + Send + 'static
+{
+	submit_wallet_transaction(WalletAction::TransferBatch(transfers), fee_rate, f);
+}
+
+// issue nfts to target address for TEST
+pub fn issue_nfts<F>(nfts: Vec<String>, fee_rate: Option<u64>, f: F)
+where 
+	F: Fn(Result<H256, String>) + Send + 'static
+{
+	submit_wallet_transaction(WalletAction::Issue(nfts), fee_rate, f);
+}
+
+// buy nfts from user's wallet cell which is on purchase mode
+pub fn purchase_nfts<F>(count: u8, fee_rate: Option<u64>, f: F)
+where
+	F: Fn(Result<H256, String>) + Send + 'static
+{
+	submit_wallet_transaction(WalletAction::Purchase(count), fee_rate, f);
+}
+
+// reveal bought nfts from user's wallet cell which is on reveal mode
+pub fn reveal_nfts<F>(fee_rate: Option<u64>, f: F)
+where
+	F: Fn(Result<H256, String>) + Send + 'static
+{
+	submit_wallet_transaction(WalletAction::Reveal, fee_rate, f);
+}
+
+// create nft store to enable purchasing nfts in which user has no nft wallet
+pub fn create_wallet<F>(fee_rate: Option<u64>, f: F)
+where
+	F: Fn(Result<H256, String>) + Send + 'static
+{
+	submit_wallet_transaction(WalletAction::CreateWallet, fee_rate, f);
+}
+
+// challenge specified kabletop channel on-chain
+pub fn challenge_kabletop_channel<F>(
+	script_args: Vec<u8>, challenger: u8, operations: Vec<String>, signed_rounds: Vec<(Round, Signature)>, fee_rate: Option<u64>, f: F
+) where
+	F: Fn(Result<H256, String>) + Send + 'static
+{
+	thread::spawn(move || {
+		let tx = block_on(build_tx_challenge_channel(script_args, challenger, operations.into(), signed_rounds))
+			.map_err(|err| err.to_string())
+			.and_then(|tx| sign_builder_transaction(tx, fee_rate));
+		match tx {
+			Ok(tx) => {
+				// write tx to file for debug
+				let json_tx = ckb_jsonrpc_types::TransactionView::from(tx.clone());
+				let json = serde_json::to_string_pretty(&json_tx).expect("jsonify");
+				std::fs::write("challenge_kabletop_channel.json", json).expect("write json file");
+
+				match push_transaction(tx) {
+					Ok(hash) => f(Ok(hash)),
+					Err(err) => f(Err(err.to_string()))
+				}
+			},
+			Err(err) => f(Err(err))
+		}
+	});
+}
+
+pub fn close_challenged_kabletop_channel<F>(
+	script_args: Vec<u8>, winner: u8, from_challenge: bool, signed_rounds: Vec<(Round, Signature)>, fee_rate: Option<u64>, f: F
+) where
+	F: Fn(Result<H256, String>) + Send + 'static
+{
+	// // for debug
+	// println!("\n===========================\nprinting operations:");
+	// signed_rounds
+	// 	.iter()
+	// 	.enumerate()
+	// 	.for_each(|(i, (round, _))| {
+	// 		println!("=> round {} for user {} <=", i + 1, u8::from(round.user_type()));
+	// 		let operations: Vec<String> = {
+	// 			let operations: Vec<Vec<u8>> = round.operations().into();
+	// 			match operations.into_iter().map(|v| String::from_utf8(v)).collect::<Result<Vec<_>, _>>() {
+	// 				Ok(value) => value,
+	// 				Err(_)    => return
+	// 			}
+	// 		};
+	// 		for code in operations {
+	// 			println!("{}", code);
+	// 		}
+	// 	});
+	// println!("===========================\n");
+	// // debug end
+
+	thread::spawn(move || {
+		let tx = block_on(build_tx_close_channel(script_args.clone(), signed_rounds, winner, from_challenge))
+			.map_err(|err| err.to_string())
+			.and_then(|tx| sign_builder_transaction(tx, fee_rate));
+		match tx {
+			Ok(tx) => {
+				// write tx to file for debug
+				let json_tx = ckb_jsonrpc_types::TransactionView::from(tx.clone());
+				let json = serde_json::to_string_pretty(&json_tx).expect("jsonify");
+				std::fs::write("close_challenged_kabletop_channel.json", json).expect("write json file");
+
+				match push_transaction(tx) {
+					Ok(hash) => {
+						record_closed_channel(&script_args);
+						f(Ok(hash))
+					},
+					Err(err) => f(Err(err.to_string()))
+				}
+			},
+			Err(err) => return f(Err(err))
+		}
+	});
+}
+
+// check the lock_script generated by specified script_args is matched with one live cell on chain
+pub fn get_kabletop_challenge_data(script_args: Vec<u8>) -> BoxFuture<'static, Result<(bool, Option<Challenge>), String>> {
+	let lock_script = kabletop_script(script_args);
+	Box::pin(async move {
+		match rpc::get_live_cells(&lock_script, None, 1) {
+			Ok(channel) => {
+				if channel.is_empty() {
+					Ok((false, None))
+				} else {
+					let (_, _, data) = channel.get(0).unwrap();
+					let challenge = match Challenge::from_slice(data) {
+						Ok(data) => Some(data),
+						Err(_)   => None
+					};
+					Ok((true, challenge))
+				}
+			},
+			Err(err) => Err(err.to_string())
+		}
+	})
+}
@@ -0,0 +1,172 @@
+use super::{
+	rpc, tracker::confirmation_depth, wallet::{
+		owned_nfts, wallet_status, online_capacity, get_kabletop_challenge_data, WalletStatus
+	}
+};
+use crate::cache;
+use futures::executor::block_on;
+use std::{
+	thread, time::Duration, collections::HashMap, sync::{
+		Mutex, Arc
+	}
+};
+
+const DEFAULT_INTERVAL: u64 = 3;
+
+// the user related chain state which is re-queried on every new block
+#[derive(Clone, PartialEq, Debug)]
+pub enum ChainEvent {
+	Capacity(u64),
+	OwnedNfts(HashMap<String, u32>),
+	WalletStatus(WalletStatus),
+	ChannelCell(bool, Option<u8>)
+}
+
+impl ChainEvent {
+	fn kind(&self) -> &'static str {
+		match self {
+			ChainEvent::Capacity(_)       => "capacity",
+			ChainEvent::OwnedNfts(_)      => "owned_nfts",
+			ChainEvent::WalletStatus(..)  => "wallet_status",
+			ChainEvent::ChannelCell(..)   => "channel_cell"
+		}
+	}
+}
+
+struct Watcher {
+	interval: u64,
+	running:  bool,
+	tip:      u64,
+	forced:   bool,
+	emitted:  HashMap<&'static str, ChainEvent>,
+	pending:  HashMap<&'static str, (ChainEvent, u64)>,
+	listener: Option<Arc<dyn Fn(ChainEvent) + Send + Sync + 'static>>
+}
+
+lazy_static! {
+	static ref WATCHER: Mutex<Watcher> = Mutex::new(Watcher {
+		interval: DEFAULT_INTERVAL,
+		running:  false,
+		tip:      0,
+		forced:   true,
+		emitted:  HashMap::new(),
+		pending:  HashMap::new(),
+		listener: None
+	});
+}
+
+// set seconds between two checks of tip block number
+pub fn set_watch_interval(interval: u64) {
+	WATCHER.lock().unwrap().interval = std::cmp::max(interval, 1);
+}
+
+// set a function to receive changes of chain state
+pub fn set_chain_event_listener<F>(listener: F)
+where
+	F: Fn(ChainEvent) + Send + Sync + 'static
+{
+	WATCHER.lock().unwrap().listener = Some(Arc::new(listener));
+}
+
+// make watcher re-query chain state at next check even if no new block arrives, and forget the
+// emitted state so that all of it is emitted again, e.g. after switching account
+pub fn refresh_chain_state() {
+	let mut watcher = WATCHER.lock().unwrap();
+	watcher.forced = true;
+	watcher.emitted.clear();
+	watcher.pending.clear();
+}
+
+// the owned nfts which are emitted last time
+pub fn latest_owned_nfts() -> Option<HashMap<String, u32>> {
+	match WATCHER.lock().unwrap().emitted.get("owned_nfts") {
+		Some(ChainEvent::OwnedNfts(nfts)) => Some(nfts.clone()),
+		_                                 => None
+	}
+}
+
+// start the only background thread which follows tip block number
+pub fn start_chain_watcher() {
+	let mut watcher = WATCHER.lock().unwrap();
+	if !watcher.running {
+		watcher.running = true;
+		thread::spawn(watch_chain);
+	}
+}
+
+fn query_chain_state() -> Vec<Result<ChainEvent, String>> {
+	let mut events = vec![
+		online_capacity().map(ChainEvent::Capacity),
+		owned_nfts().map(ChainEvent::OwnedNfts),
+		wallet_status().map(ChainEvent::WalletStatus)
+	];
+	let script_args = cache::get_clone().script_args;
+	if !script_args.is_empty() {
+		let channel = block_on(get_kabletop_challenge_data(script_args))
+			.map(|(alive, challenge)| ChainEvent::ChannelCell(alive, challenge.map(|value| u8::from(value.challenger()))));
+		events.push(channel);
+	}
+	events
+}
+
+// changed state is emitted once it stays unchanged for confirmation depth blocks, the first state is emitted at once
+fn watch_chain() {
+	loop {
+		let interval = WATCHER.lock().unwrap().interval;
+		let tip = match rpc::get_tip_block_number() {
+			Ok(tip) => tip,
+			Err(err) => {
+				println!("chain watcher error: {}", err);
+				thread::sleep(Duration::from_secs(interval));
+				continue
+			}
+		};
+		let forced = {
+			let mut watcher = WATCHER.lock().unwrap();
+			let forced = watcher.forced || tip != watcher.tip;
+			watcher.forced = false;
+			watcher.tip = tip;
+			forced
+		};
+		if forced {
+			let events = query_chain_state();
+			let depth = confirmation_depth();
+			let mut confirmed_events = vec![];
+			let mut watcher = WATCHER.lock().unwrap();
+			for event in events {
+				let event = match event {
+					Ok(event) => event,
+					Err(err)  => {
+						println!("chain watcher error: {}", err);
+						continue
+					}
+				};
+				let kind = event.kind();
+				if watcher.emitted.get(kind) == Some(&event) {
+					watcher.pending.remove(kind);
+					continue
+				}
+				let confirmed = match watcher.pending.get(kind) {
+					_ if !watcher.emitted.contains_key(kind) => true,
+					Some((value, since)) if value == &event  => tip + 1 >= since + depth,
+					_                                        => {
+						watcher.pending.insert(kind, (event.clone(), tip));
+						depth <= 1
+					}
+				};
+				if confirmed {
+					watcher.pending.remove(kind);
+					watcher.emitted.insert(kind, event.clone());
+					confirmed_events.push(event);
+				}
+			}
+			// listener is called without lock, so that it can refresh chain state
+			let listener = watcher.listener.clone();
+			drop(watcher);
+			if let Some(listener) = listener {
+				confirmed_events.into_iter().for_each(|event| listener(event));
+			}
+		}
+		thread::sleep(Duration::from_secs(interval));
+	}
+}
\ No newline at end of file
@@ -0,0 +1,65 @@
+use aes_gcm::{
+	Aes256Gcm, Key, Nonce, aead::{
+		Aead, NewAead
+	}
+};
+use crate::account::active_signer;
+use ckb_crypto::secp::Signature;
+use ckb_types::H256;
+use rand::RngCore;
+
+const SALT_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+
+// derive a 32 bytes symmetric key from user password
+fn derive_key(password: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+	let params = scrypt::Params::new(14, 8, 1).map_err(|err| err.to_string())?;
+	let mut key = [0u8; 32];
+	scrypt::scrypt(password.as_bytes(), salt, &params, &mut key).map_err(|err| err.to_string())?;
+	Ok(key)
+}
+
+// encrypt content by password, the output is composed by [salt, nonce, ciphertext]
+pub fn encrypt(password: &str, content: &[u8]) -> Result<Vec<u8>, String> {
+	let mut salt = [0u8; SALT_SIZE];
+	let mut nonce = [0u8; NONCE_SIZE];
+	rand::thread_rng().fill_bytes(&mut salt);
+	rand::thread_rng().fill_bytes(&mut nonce);
+	let key = derive_key(password, &salt)?;
+	let ciphertext = Aes256Gcm::new(Key::from_slice(&key))
+		.encrypt(Nonce::from_slice(&nonce), content)
+		.map_err(|_| String::from("encrypt content failed"))?;
+	Ok([&salt[..], &nonce[..], &ciphertext[..]].concat())
+}
+
+// decrypt content which is encrypted by the same password
+pub fn decrypt(password: &str, content: &[u8]) -> Result<Vec<u8>, String> {
+	if content.len() < SALT_SIZE + NONCE_SIZE {
+		return Err(String::from("encrypted content is too short"));
+	}
+	let (salt, content) = content.split_at(SALT_SIZE);
+	let (nonce, ciphertext) = content.split_at(NONCE_SIZE);
+	let key = derive_key(password, salt)?;
+	Aes256Gcm::new(Key::from_slice(&key))
+		.decrypt(Nonce::from_slice(nonce), ciphertext)
+		.map_err(|_| String::from("wrong password or broken content"))
+}
+
+// sign the blake2b hash of content by user private key
+pub fn sign(content: &[u8]) -> Result<Vec<u8>, String> {
+	let message = H256::from(ckb_hash::blake2b_256(content));
+	let signature = active_signer().sign_message(&message)?;
+	Ok(signature.serialize())
+}
+
+// recover the pkhash of the signer who signed content
+pub fn recover_signer(content: &[u8], signature: &[u8]) -> Result<[u8; 20], String> {
+	let message = H256::from(ckb_hash::blake2b_256(content));
+	let pubkey = Signature::from_slice(signature)
+		.map_err(|err| err.to_string())?
+		.recover(&message)
+		.map_err(|err| err.to_string())?;
+	let mut pkhash = [0u8; 20];
+	pkhash.copy_from_slice(&ckb_hash::blake2b_256(pubkey.serialize())[..20]);
+	Ok(pkhash)
+}
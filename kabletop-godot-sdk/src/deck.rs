@@ -0,0 +1,120 @@
+use crate::{
+	account::db_dir, cache::ChannelCache, lua::highlevel::Lua, ckb::{
+		latest_owned_nfts, owned_nfts
+	}
+};
+use std::{
+	sync::Mutex, collections::HashMap
+};
+
+lazy_static! {
+	static ref RULES: Mutex<Option<String>> = Mutex::new(None);
+}
+
+// decks are kept out of db directory itself, whose json files are all channel caches
+fn decks_path() -> String {
+	format!("{}/decks/decks.json", db_dir())
+}
+
+// where decks were saved before, it's still read until decks are stored again
+fn legacy_decks_path() -> String {
+	format!("{}/decks.json", db_dir())
+}
+
+fn store_decks(decks: &HashMap<String, Vec<String>>) -> Result<(), String> {
+	std::fs::create_dir_all(format!("{}/decks", db_dir())).map_err(|err| err.to_string())?;
+	let content = serde_json::to_string_pretty(decks).map_err(|err| err.to_string())?;
+	std::fs::write(decks_path(), content).map_err(|err| err.to_string())?;
+	let _ = std::fs::remove_file(legacy_decks_path());
+	Ok(())
+}
+
+// all named decks of active account, each deck is a list of nft hashes in which one hash can repeat
+pub fn list_decks() -> Result<HashMap<String, Vec<String>>, String> {
+	for path in vec![decks_path(), legacy_decks_path()] {
+		if let Ok(content) = std::fs::read_to_string(path.clone()) {
+			return serde_json::from_str(content.as_str()).map_err(|err| format!("{} => {}", err, path))
+		}
+	}
+	Ok(HashMap::new())
+}
+
+pub fn save_deck(name: String, nfts: Vec<String>) -> Result<(), String> {
+	if name.is_empty() {
+		return Err(String::from("empty deck name"));
+	}
+	let mut decks = list_decks()?;
+	decks.insert(name, nfts);
+	store_decks(&decks)
+}
+
+pub fn delete_deck(name: String) -> Result<bool, String> {
+	let mut decks = list_decks()?;
+	let deleted = decks.remove(&name).is_some();
+	if deleted {
+		store_decks(&decks)?;
+	}
+	Ok(deleted)
+}
+
+pub fn load_deck(name: String) -> Result<Vec<String>, String> {
+	list_decks()?
+		.remove(&name)
+		.ok_or(format!("deck {} not found", name))
+}
+
+// set lua file which defines global function check_deck(nfts), it returns an error string to reject the deck
+pub fn set_deck_rules(path: Option<String>) -> Result<(), String> {
+	if let Some(path) = &path {
+		if !path.ends_with(".lua") || !std::path::Path::new(path).is_file() {
+			return Err(format!("invalid lua file {}", path));
+		}
+	}
+	*RULES.lock().unwrap() = path;
+	Ok(())
+}
+
+// check deck against owned nfts, channel limitation and lua rules, every violation is described in returned list
+pub fn validate_deck(nfts: &[String]) -> Result<Vec<String>, String> {
+	let mut issues = vec![];
+	if nfts.is_empty() {
+		issues.push(String::from("empty nfts"));
+		return Ok(issues);
+	}
+	let max_count = ChannelCache::default().max_nfts_count as usize;
+	if nfts.len() > max_count {
+		issues.push(format!("deck has {} nfts which exceed the max count {}", nfts.len(), max_count));
+	}
+	let owned = match latest_owned_nfts() {
+		Some(owned) => owned,
+		None        => owned_nfts()?
+	};
+	let mut counts: Vec<(String, u32)> = vec![];
+	for nft in nfts {
+		if nft.len() != 40 || hex::decode(nft).is_err() {
+			issues.push(format!("invalid nft hash {}", nft));
+			continue
+		}
+		match counts.iter_mut().find(|(hash, _)| hash == nft) {
+			Some((_, count)) => *count += 1,
+			None             => counts.push((nft.clone(), 1))
+		}
+	}
+	for (nft, count) in counts {
+		let owned_count = owned.get(&nft).cloned().unwrap_or(0);
+		if count > owned_count {
+			issues.push(format!("deck needs {} of nft {} but only {} owned", count, nft, owned_count));
+		}
+	}
+	// a broken rules file can't tell whether deck is legal, so it rejects the deck as well
+	if let Some(path) = RULES.lock().unwrap().clone() {
+		let lua = Lua::new(0, 0);
+		match lua.try_boost(path.clone()).and_then(|_| lua.call_with_strings("check_deck", nfts.to_vec())) {
+			Ok(Some(issue)) => issues.push(issue),
+			Ok(None)        => (),
+			Err(err)        => issues.push(format!("deck rules {} failed: {}", path, err))
+		}
+		lua.close();
+	}
+	Ok(issues)
+}
\ No newline at end of file
@@ -22,4 +22,8 @@ macro_rules! println {
 pub mod p2p;
 pub mod lua;
 pub mod cache;
-pub mod ckb;
\ No newline at end of file
+pub mod ckb;
+pub mod crypto;
+pub mod account;
+pub mod catalog;
+pub mod deck;
\ No newline at end of file
@@ -218,6 +218,32 @@ impl Lua {
 		self.pcall(0, LUA_MULTRET);
 	}
 
+	// same as do_string, but the failure is returned instead of panicking, and the error object is popped
+	pub fn try_do_string(&self, code: &str) -> Result<(), String> {
+		unsafe {
+			let ret = match luaL_loadstring(self.L, cstr!(code)) {
+				LUA_OK => lua_pcallk(self.L, 0, LUA_MULTRET, self.herr, ptr::null_mut(), None),
+				ret    => ret
+			};
+			if ret == LUA_OK {
+				return Ok(());
+			}
+			let message = if lua_isstring(self.L, -1) != 0 {
+				self.to_string(-1)
+			} else {
+				match ret {
+					LUA_ERRRUN    => String::from("lua runtime error"),
+					LUA_ERRSYNTAX => String::from("lua syntax error"),
+					LUA_ERRMEM    => String::from("lua memory error"),
+					LUA_ERRERR    => String::from("lua common error"),
+					_             => format!("unknown error code ({})", ret)
+				}
+			};
+			lua_settop(self.L, -2);
+			Err(message)
+		}
+	}
+
 	pub fn set_error_func(&mut self, errorfunc: lua_CFunction) {
 		unsafe {
 			lua_pushcclosure(self.L, errorfunc, 0);
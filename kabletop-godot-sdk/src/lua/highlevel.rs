@@ -53,6 +53,45 @@ impl Lua {
 		self.lua.do_string(code.as_str());
 	}
 
+	// load lua file like boost, but a missing file or an error in it is returned instead of panicking
+	pub fn try_boost(&self, lua_path: String) -> Result<(), String> {
+		let mut root = PathBuf::from(lua_path.clone());
+		root.pop();
+		self.lua.set_root(root.to_str().ok_or(format!("invalid lua path {}", lua_path))?);
+		let code = std::fs::read_to_string(&lua_path).map_err(|err| format!("{} => {}", err, lua_path))?;
+		self.lua.try_do_string(code.as_str())
+	}
+
+	// call a global lua function with a string array, and collect the string it returns if any, an undefined
+	// function or an error raised in it is returned as error
+	pub fn call_with_strings(&self, function: &str, value: Vec<String>) -> Result<Option<String>, String> {
+		if !self.lua.get_global(function, true) {
+			self.lua.remove(-1);
+			return Err(format!("lua function {} is not defined", function));
+		}
+		self.lua.push_string_array(value);
+		self.lua.set_global("_arguments", -1, true);
+		self.lua.try_do_string(format!("_result = {}(_arguments)", function).as_str())?;
+		let result = if self.lua.get_global("_result", false) && self.lua.is_string(-1) {
+			Some(self.lua.to_string(-1))
+		} else {
+			None
+		};
+		self.lua.remove(-1);
+		Ok(result)
+	}
+
+	// read the [_winner] global variable which game scripts set once the game is over, 0 means no winner yet
+	pub fn winner(&self) -> u8 {
+		self.lua.get_global("_winner", false);
+		let winner = self.lua.to_int64(-1);
+		self.lua.remove(-1);
+		match winner {
+			1 | 2 => winner as u8,
+			_     => 0
+		}
+	}
+
 	// run a concrete lua code and collect the events emited from the code for the caller
 	pub fn run(&self, lua_code: String) -> Vec<Vec<ffi::lua_Event>> {
 		self.lua.do_string(lua_code.as_str());
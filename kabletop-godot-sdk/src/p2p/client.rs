@@ -32,6 +32,9 @@ where
 		.register("sync_p2p_message", reply::sync_p2p_message)
 		.register("close_kabletop_channel", reply::close_kabletop_channel)
 		.register("notify_game_over", reply::notify_game_over)
+		.register("offer_trade", reply::offer_trade)
+		.register("submit_trade", reply::submit_trade)
+		.register("propose_swap", reply::propose_swap)
 		.register_call("prepare_kabletop_channel")
 		.register_call("open_kabletop_channel")
 		.register_call("close_kabletop_channel")
@@ -39,6 +42,9 @@ where
 		.register_call("sync_operation")
 		.register_call("sync_p2p_message")
 		.register_call("notify_game_over")
+		.register_call("offer_trade")
+		.register_call("submit_trade")
+		.register_call("propose_swap")
 		// for relay connections
 		.register("prepare_kabletop_channel", reply::prepare_kabletop_channel)
 		.register("open_kabletop_channel", reply::open_kabletop_channel)
@@ -74,9 +80,9 @@ pub fn open_kabletop_channel() -> Result<[u8; 32], String> {
 	)
 }
 
-pub fn close_kabletop_channel() -> Result<[u8; 32], String> {
+pub fn close_kabletop_channel(fee_rate: Option<u64>) -> Result<[u8; 32], String> {
 	send::close_kabletop_channel(
-		CLIENT.lock().unwrap().as_ref().unwrap()
+		CLIENT.lock().unwrap().as_ref().unwrap(), fee_rate
 	)
 }
 
@@ -106,6 +112,18 @@ pub fn sync_p2p_message(
 	)
 }
 
+pub fn offer_trade(nfts: Vec<String>, price: u64) -> Result<[u8; 32], String> {
+	send::offer_trade(
+		CLIENT.lock().unwrap().as_ref().unwrap(), nfts, price
+	)
+}
+
+pub fn propose_swap(gives: Vec<String>, takes: Vec<String>) -> Result<[u8; 32], String> {
+	send::propose_swap(
+		CLIENT.lock().unwrap().as_ref().unwrap(), gives, takes
+	)
+}
+
 //////////////////////////////////////////////
 // for relay connection sending messages
 //////////////////////////////////////////////
@@ -1,6 +1,6 @@
 use kabletop_ckb_sdk::{
 	config::VARS, p2p::Caller, ckb::{
-		rpc::methods as ckb, transaction::{
+		transaction::{
 			builder::build_tx_close_channel, channel::{
 				interact as channel, protocol::Args
 			}
@@ -8,7 +8,12 @@ use kabletop_ckb_sdk::{
 	}
 };
 use crate::{
-	cache, p2p::protocol::types::{
+	cache, account::{
+		active_pubhash, active_signer
+	}, ckb::{
+		rpc, track_transaction, wait_transaction, apply_fee_rate, record_open_channel, record_closed_channel, sign_builder_transaction, build_trade_offer, complete_trade_offer, sign_completed_trade,
+		expect_trade_answer, remember_completed_trade, is_completed_trade, TradeOffer, build_swap_proposal, complete_swap_proposal, sign_completed_swap, SwapProposal
+	}, p2p::protocol::types::{
 		request, response, GodotType
 	}
 };
@@ -22,19 +27,14 @@ use std::{
 	thread, time, collections::HashMap, sync::Mutex, convert::TryInto
 };
 use futures::{
-	executor::block_on, future::BoxFuture
+	Future, executor::block_on, future::BoxFuture
 };
 use ckb_crypto::secp::Signature;
 use molecule::prelude::Entity as MolEntity;
 
 fn check_transaction_committed_or_not(hash: &H256) -> bool {
-	for _ in 0..20 {
-		if ckb::get_transaction(hash.pack()).is_ok() {
-			return true;
-		}
-		thread::sleep(time::Duration::from_secs(5));
-	}
-	return false;
+	track_transaction(hash.clone());
+	wait_transaction(hash).is_ok()
 }
 
 pub mod send {
@@ -59,6 +59,11 @@ pub mod send {
 			store.user_pkhash,
 			hashes
 		)).map_err(|err| format!("prepare_channel_tx -> {}", err))?;
+		// user pays for the part prepared here, and opposite pays for the rest when completing it
+		let tx = match store.fee_rate {
+			Some(fee_rate) => apply_fee_rate(tx, fee_rate).map_err(|err| format!("apply_fee_rate -> {}", err))?,
+			None           => tx
+		};
 		let value: response::CompleteAndSignChannel = caller.call(
 			"prepare_kabletop_channel", request::PrepareChannel {
 				tx: tx.into()
@@ -71,13 +76,12 @@ pub mod send {
 		let kabletop_args = Args::new_unchecked(lock_args.into());
 		cache::set_opponent_pkhash(kabletop_args.user2_pkhash().into());
 		cache::set_opponent_nfts(kabletop_args.user2_nfts().into());
-		let tx = channel::sign_channel_tx(
+		let tx = active_signer().sign_channel_tx(
 			tx,
 			store.staking_ckb,
 			store.bet_ckb,
 			store.max_nfts_count,
-			store.user_nfts,
-			&VARS.common.user_key.privkey
+			store.user_nfts
 		).map_err(|err| format!("sign_channel_tx -> {}", err))?;
 
 		// write tx to file for debug
@@ -85,10 +89,10 @@ pub mod send {
         let json = serde_json::to_string_pretty(&json_tx).expect("jsonify");
         std::fs::write("open_kabletop_channel.json", json).expect("write json file");
 
-		let hash = ckb::send_transaction(tx.data())
+		let hash = rpc::send_transaction(&tx)
 			.map_err(|err| format!("send_transaction -> {}", err))?;
 		if !check_transaction_committed_or_not(&hash) {
-			return Err(String::from("send_transaction successed, but no committed transaction found in CKB network in 100s"));
+			return Err(String::from("send_transaction successed, but transaction is rejected by CKB network"));
 		}
 		let value: response::OpenChannel = caller.call(
 			"open_kabletop_channel", request::SignAndSubmitChannel {
@@ -104,11 +108,12 @@ pub mod send {
 			kabletop.lock().args().raw_data().to_vec(),
 			kabletop.capacity().unpack()
 		);
+		record_open_channel(&kabletop.lock().args().raw_data(), store.staking_ckb);
 		Ok(tx.hash().unpack())
 	}
 
 	// try to close a state channel between client and server
-	pub fn close_kabletop_channel<T: Caller>(caller: &T) -> Result<[u8; 32], String> {
+	pub fn close_kabletop_channel<T: Caller>(caller: &T, fee_rate: Option<u64>) -> Result<[u8; 32], String> {
 		let store = cache::get_clone();
 		let tx = block_on(build_tx_close_channel(
 			store.script_args,
@@ -116,17 +121,19 @@ pub mod send {
 			store.winner,
 			false
 		)).map_err(|err| format!("build_tx_close_channel -> {}", err))?;
+		let tx = sign_builder_transaction(tx, fee_rate).map_err(|err| format!("sign_builder_transaction -> {}", err))?;
 
 		// write tx to file for debug
         let json_tx = ckb_jsonrpc_types::TransactionView::from(tx.clone());
         let json = serde_json::to_string_pretty(&json_tx).expect("jsonify");
         std::fs::write("close_kabletop_channel.json", json).expect("write json file");
 
-		let hash = ckb::send_transaction(tx.data())
+		let hash = rpc::send_transaction(&tx)
 			.map_err(|err| format!("send_transaction -> {}", err))?;
 		if !check_transaction_committed_or_not(&hash) {
-			return Err(String::from("send_transaction successed, but no committed transaction found in CKB network in 100s"));
+			return Err(String::from("send_transaction successed, but transaction is rejected by CKB network"));
 		}
+		record_closed_channel(&store.script_args);
 		let value: response::CloseChannel = caller.call(
 			"close_kabletop_channel", request::CloseChannel {
 				tx:           tx.into(),
@@ -135,6 +142,7 @@ pub mod send {
 		if !value.result {
 			return Err(String::from("opposite REFUSED to apply closing kabeltop channel"));
 		}
+		reply::trigger_hook("close_kabletop_channel", hash.as_bytes().to_vec());
 		Ok(hash.pack().unpack())
 	}
 
@@ -163,6 +171,9 @@ pub mod send {
 	// send operations to verify and make round move forward
 	pub fn switch_round<T: Caller>(caller: &T) -> Result<[u8; 65], String> {
 		let store = cache::get_clone();
+		if store.round_owner != store.user_type {
+			return Err(format!("round {} is owned by opponent, user can't switch it", store.round_index));
+		}
 		let value: response::OpenRound = caller.call(
 			"switch_round", request::CloseRound {
 				operations: store.round_operations.clone()
@@ -181,7 +192,7 @@ pub mod send {
 
 	// synchronize operations in current round
 	pub fn sync_operation<T: Caller>(caller: &T, operation: String) -> Result<(), String> {
-		cache::commit_user_operation(operation.clone());
+		cache::commit_user_operation(operation.clone())?;
 		let _: response::ApplyOperation = caller.call(
 			"sync_operation", request::PushOperation {
 				operation: operation
@@ -199,6 +210,72 @@ pub mod send {
 			}).map_err(|err| format!("SendP2pMessage -> {}", err))?;
 		Ok((value.message, value.parameters))
 	}
+
+	// offer nfts to opposite at price, and submit the trade transaction once opposite completes and signs it
+	pub fn offer_trade<T: Caller>(caller: &T, nfts: Vec<String>, price: u64) -> Result<[u8; 32], String> {
+		let (tx, nft_index) = build_trade_offer(&nfts, price)?;
+		let value: response::CompleteAndSignTrade = caller.call(
+			"offer_trade", request::OfferTrade {
+				tx:        tx.clone().into(),
+				nft_index: nft_index as u32,
+				seller:    active_pubhash(),
+				nfts,
+				price
+			}).map_err(|err| format!("OfferTrade -> {}", err))?;
+		let completed = {
+			let tx: Transaction = value.tx.inner.into();
+			tx.into_view()
+		};
+		let tx = sign_completed_trade(&tx, nft_index, completed)?;
+		let hash = rpc::send_transaction(&tx)
+			.map_err(|err| format!("send_transaction -> {}", err))?;
+		if !check_transaction_committed_or_not(&hash) {
+			return Err(String::from("send_transaction successed, but transaction is rejected by CKB network"));
+		}
+		let value: response::SettleTrade = caller.call(
+			"submit_trade", request::SubmitTrade {
+				tx: tx.clone().into()
+			}).map_err(|err| format!("SubmitTrade -> {}", err))?;
+		if !value.result {
+			return Err(String::from("opposite can't find the committed trade transaction"));
+		}
+		Ok(tx.hash().unpack())
+	}
+
+	// propose to swap nfts with opposite, the transaction signed by both sides can be submitted by either of them
+	pub fn propose_swap<T: Caller>(caller: &T, gives: Vec<String>, takes: Vec<String>) -> Result<[u8; 32], String> {
+		let (tx, nft_index) = build_swap_proposal(&gives, &takes)?;
+		let proposal = SwapProposal {
+			proposer:  active_pubhash(),
+			gives,
+			takes,
+			answer_id: 0
+		};
+		let value: response::CompleteAndSignTrade = caller.call(
+			"propose_swap", request::ProposeSwap {
+				tx:        tx.into(),
+				nft_index: nft_index as u32,
+				proposer:  proposal.proposer,
+				gives:     proposal.gives.clone(),
+				takes:     proposal.takes.clone()
+			}).map_err(|err| format!("ProposeSwap -> {}", err))?;
+		let completed = {
+			let tx: Transaction = value.tx.inner.into();
+			tx.into_view()
+		};
+		let tx = sign_completed_swap(completed, nft_index, &proposal)?;
+		if let Err(err) = rpc::send_transaction(&tx) {
+			println!("send swap transaction failed, leave it to opposite: {}", err);
+		}
+		let value: response::SettleTrade = caller.call(
+			"submit_trade", request::SubmitTrade {
+				tx: tx.clone().into()
+			}).map_err(|err| format!("SubmitTrade -> {}", err))?;
+		if !value.result {
+			return Err(String::from("swap transaction is rejected by CKB network"));
+		}
+		Ok(tx.hash().unpack())
+	}
 }
 
 pub mod reply {
@@ -217,11 +294,61 @@ pub mod reply {
 		}
 	}
 
+	// only for the integration tests, which run both peers in one process
+	#[cfg(feature = "harness")]
+	pub mod scope {
+		use super::SCOPE;
+
+		// surround every reply with enter and leave, which lets peers living in one process swap their native states
+		pub fn set<E, L>(enter: E, leave: L)
+		where
+			E: Fn() + Sync + Send + 'static,
+			L: Fn() + Sync + Send + 'static
+		{
+			*SCOPE.lock().unwrap() = Some((Box::new(enter), Box::new(leave)));
+		}
+
+		pub fn clear() {
+			*SCOPE.lock().unwrap() = None;
+		}
+	}
+
 	lazy_static! {
 		static ref HOOKS: Mutex<HashMap<String, Vec<Box<dyn Fn(&Vec<u8>) + Sync + Send>>>> = Mutex::new(HashMap::new());
 	}
 
-	fn trigger_hook(method: &str, param: Vec<u8>) {
+	#[cfg(feature = "harness")]
+	lazy_static! {
+		static ref SCOPE: Mutex<Option<(Box<dyn Fn() + Sync + Send>, Box<dyn Fn() + Sync + Send>)>> = Mutex::new(None);
+	}
+
+	#[cfg(not(feature = "harness"))]
+	fn scoped<F>(reply: F) -> BoxFuture<'static, Result<Value, String>>
+	where
+		F: Future<Output = Result<Value, String>> + Send + 'static
+	{
+		Box::pin(reply)
+	}
+
+	#[cfg(feature = "harness")]
+	fn scoped<F>(reply: F) -> BoxFuture<'static, Result<Value, String>>
+	where
+		F: Future<Output = Result<Value, String>> + Send + 'static
+	{
+		Box::pin(async move {
+			if let Some((enter, _)) = &*SCOPE.lock().unwrap() {
+				enter();
+			}
+			let result = reply.await;
+			if let Some((_, leave)) = &*SCOPE.lock().unwrap() {
+				leave();
+			}
+			result
+		})
+	}
+
+	// hooks of a reply also run when this side sends the request, if it ends in the same way
+	pub(super) fn trigger_hook(method: &str, param: Vec<u8>) {
 		let method = String::from(method);
 		thread::spawn(move || {
 			if let Some(hooks) = HOOKS.lock().unwrap().get(&method) {
@@ -234,10 +361,16 @@ pub mod reply {
 
 	// response operation of openning kabletop channel
 	pub fn prepare_kabletop_channel(_: i32, value: Value) -> BoxFuture<'static, Result<Value, String>> {
-		Box::pin(async {
+		scoped(async {
 			let value: request::PrepareChannel = from_value(value)
 				.map_err(|err| format!("deserialize PrepareChannel -> {}", err))?;
 			let store = cache::get_clone();
+			// deck of the listening side is validated here so that rpc and lua never block the caller of listen
+			let nfts = store.user_nfts.iter().map(hex::encode).collect::<Vec<_>>();
+			let issues = crate::deck::validate_deck(&nfts)?;
+			if !issues.is_empty() {
+				return Err(issues.join("; "));
+			}
 			let hashes = VARS
 				.luacodes
 				.iter()
@@ -260,13 +393,16 @@ pub mod reply {
 				store.user_pkhash,
 				hashes
 			).await.map_err(|err| format!("complete_channel_tx -> {}", err))?;
-			let tx = channel::sign_channel_tx(
+			let tx = match store.fee_rate {
+				Some(fee_rate) => apply_fee_rate(tx, fee_rate).map_err(|err| format!("apply_fee_rate -> {}", err))?,
+				None           => tx
+			};
+			let tx = active_signer().sign_channel_tx(
 				tx,
 				store.staking_ckb,
 				store.bet_ckb,
 				store.max_nfts_count,
-				store.user_nfts,
-				&VARS.common.user_key.privkey
+				store.user_nfts
 			).map_err(|err| format!("sign_channel_tx -> {}", err))?;
 			let kabletop = tx.output(0).unwrap();
 			cache::set_channel_verification(
@@ -284,11 +420,15 @@ pub mod reply {
 
 	// response operation of submitting open_kabletop_channel transaction
 	pub fn open_kabletop_channel(_: i32, value: Value) -> BoxFuture<'static, Result<Value, String>> {
-		Box::pin(async {
+		scoped(async {
 			let value: request::SignAndSubmitChannel = from_value(value)
 				.map_err(|err| format!("deserialize open_kabletop_channel -> {}", err))?;
 			let hash = value.tx.hash;
 			let ok = check_transaction_committed_or_not(&hash);
+			if ok {
+				let store = cache::get_clone();
+				record_open_channel(&store.script_args, store.staking_ckb);
+			}
 			trigger_hook("open_kabletop_channel", hash.as_bytes().to_vec());
 			Ok(json!(response::OpenChannel {
 				result: ok
@@ -298,7 +438,7 @@ pub mod reply {
 
 	// response operation of submitting close_kabletop_channel transaction
 	pub fn close_kabletop_channel(_: i32, value: Value) -> BoxFuture<'static, Result<Value, String>> {
-		Box::pin(async {
+		scoped(async {
 			let value: request::CloseChannel = from_value(value)
 				.map_err(|err| format!("deserialize close_kabletop_channel -> {}", err))?;
 			if cache::get_clone().channel_hash != value.channel_hash {
@@ -306,6 +446,9 @@ pub mod reply {
 			}
 			let hash = value.tx.hash;
 			let ok = check_transaction_committed_or_not(&hash);
+			if ok {
+				record_closed_channel(&cache::get_clone().script_args);
+			}
 			trigger_hook("close_kabletop_channel", hash.as_bytes().to_vec());
 			Ok(json!(response::CloseChannel {
 				result: ok
@@ -315,7 +458,7 @@ pub mod reply {
 
 	// response verification of wether game has finished 
 	pub fn notify_game_over(_: i32, value: Value) -> BoxFuture<'static, Result<Value, String>> {
-		Box::pin(async {
+		scoped(async {
 			let value: request::CloseGame = from_value(value)
 				.map_err(|err| format!("deserialize verify_game_over -> {}", err))?;
 			let mut store = cache::get_clone();
@@ -336,11 +479,10 @@ pub mod reply {
 				}
 			}
 			let next_round = channel::make_round(store.opponent_type, store.round_operations);
-			let signature = channel::sign_channel_round(
+			let signature = active_signer().sign_round(
 				store.script_hash.pack(),
 				cache::get_kabletop_signed_rounds()?,
-				next_round,
-				&VARS.common.user_key.privkey
+				next_round
 			).map_err(|err| format!("sign_channel_round -> {}", err))?;
 			cache::commit_opponent_round(signature.clone());
 			trigger_hook("game_over", vec![store.winner]);
@@ -353,19 +495,21 @@ pub mod reply {
 
 	// response operation of switching kabletop round
 	pub fn switch_round(_: i32, value: Value) -> BoxFuture<'static, Result<Value, String>> {
-		Box::pin(async {
+		scoped(async {
 			let value: request::CloseRound = from_value(value)
 				.map_err(|err| format!("deserialize switch_round -> {}", err))?;
 			let store = cache::get_clone();
+			if store.round_owner != store.opponent_type {
+				return Err(format!("round {} is owned by native, opposite can't switch it", store.round_index));
+			}
 			if value.operations != store.round_operations {
 				return Err(String::from("opposite and native operations are mismatched"));
 			}
 			let next_round = channel::make_round(store.opponent_type, store.round_operations);
-			let signature = channel::sign_channel_round(
+			let signature = active_signer().sign_round(
 				store.script_hash.pack(),
 				cache::get_kabletop_signed_rounds()?,
-				next_round,
-				&VARS.common.user_key.privkey
+				next_round
 			).map_err(|err| format!("sign_channel_round -> {}", err))?;
 			cache::commit_opponent_round(signature.clone());
 			trigger_hook("switch_round", signature.serialize());
@@ -377,10 +521,10 @@ pub mod reply {
 
 	// accept operations generated from current round
 	pub fn sync_operation(_: i32, value: Value) -> BoxFuture<'static, Result<Value, String>> {
-		Box::pin(async {
+		scoped(async {
 			let value: request::PushOperation = from_value(value)
 				.map_err(|err| format!("deserialize PushOperation -> {}", err))?;
-			cache::commit_opponent_operation(value.operation.clone());
+			cache::commit_opponent_operation(value.operation.clone())?;
 			trigger_hook("sync_operation", value.operation.as_bytes().to_vec());
 			Ok(json!(response::ApplyOperation {}))
 		})
@@ -388,7 +532,7 @@ pub mod reply {
 
 	// accept user-defined godot message and reply in a same type
 	pub fn sync_p2p_message(_: i32, value: Value) -> BoxFuture<'static, Result<Value, String>> {
-		Box::pin(async {
+		scoped(async {
 			let value: request::SendP2pMessage = from_value(value)
 				.map_err(|err| format!("deserialize PushP2pMessage -> {}", err))?;
 			let store = cache::GODOT_CACHE.lock().unwrap();
@@ -403,4 +547,82 @@ pub mod reply {
 			}))
 		})
 	}
+
+	// show the offer to player, and complete the trade transaction with user's ckb if player accepts it
+	pub fn offer_trade(_: i32, value: Value) -> BoxFuture<'static, Result<Value, String>> {
+		scoped(async {
+			let value: request::OfferTrade = from_value(value)
+				.map_err(|err| format!("deserialize OfferTrade -> {}", err))?;
+			let (answer_id, answer) = expect_trade_answer();
+			let offer = TradeOffer {
+				seller: value.seller,
+				nfts:   value.nfts,
+				price:  value.price,
+				answer_id
+			};
+			trigger_hook("offer_trade", serde_json::to_vec(&offer).unwrap());
+			if !answer.await.unwrap_or(false) {
+				return Err(String::from("opposite declined the trade offer"));
+			}
+			let tx = {
+				let tx: Transaction = value.tx.inner.into();
+				tx.into_view()
+			};
+			let tx = complete_trade_offer(tx, value.nft_index as usize, &offer)?;
+			remember_completed_trade(tx.hash().unpack());
+			Ok(json!(response::CompleteAndSignTrade {
+				tx: tx.into()
+			}))
+		})
+	}
+
+	// show the proposal to player, and complete the swap transaction with user's nfts if player accepts it
+	pub fn propose_swap(_: i32, value: Value) -> BoxFuture<'static, Result<Value, String>> {
+		scoped(async {
+			let value: request::ProposeSwap = from_value(value)
+				.map_err(|err| format!("deserialize ProposeSwap -> {}", err))?;
+			let (answer_id, answer) = expect_trade_answer();
+			let proposal = SwapProposal {
+				proposer: value.proposer,
+				gives:    value.gives,
+				takes:    value.takes,
+				answer_id
+			};
+			trigger_hook("propose_swap", serde_json::to_vec(&proposal).unwrap());
+			if !answer.await.unwrap_or(false) {
+				return Err(String::from("opposite declined the swap proposal"));
+			}
+			let tx = {
+				let tx: Transaction = value.tx.inner.into();
+				tx.into_view()
+			};
+			let tx = complete_swap_proposal(tx, value.nft_index as usize, &proposal)?;
+			remember_completed_trade(tx.hash().unpack());
+			Ok(json!(response::CompleteAndSignTrade {
+				tx: tx.into()
+			}))
+		})
+	}
+
+	// response operation of submitting trade transaction, which is sent again in case opposite failed to send it
+	pub fn submit_trade(_: i32, value: Value) -> BoxFuture<'static, Result<Value, String>> {
+		scoped(async {
+			let value: request::SubmitTrade = from_value(value)
+				.map_err(|err| format!("deserialize SubmitTrade -> {}", err))?;
+			let tx = {
+				let tx: Transaction = value.tx.inner.into();
+				tx.into_view()
+			};
+			let hash: H256 = tx.hash().unpack();
+			if !is_completed_trade(&hash) {
+				return Err(format!("transaction ({}) isn't a trade or swap completed by this peer", hash));
+			}
+			let _ = rpc::send_transaction(&tx);
+			let ok = check_transaction_committed_or_not(&hash);
+			trigger_hook("submit_trade", hash.as_bytes().to_vec());
+			Ok(json!(response::SettleTrade {
+				result: ok
+			}))
+		})
+	}
 }
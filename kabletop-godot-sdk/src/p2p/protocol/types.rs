@@ -62,6 +62,32 @@ pub mod request {
 		pub message:    String,
 		pub parameters: HashMap<String, GodotType>
 	}
+
+	// seller offers nfts at a price with the unsigned transaction, whose nft output is taken by buyer
+	#[derive(Serialize, Deserialize)]
+	pub struct OfferTrade {
+		pub tx:        TransactionView,
+		pub nft_index: u32,
+		pub seller:    [u8; 20],
+		pub nfts:      Vec<String>,
+		pub price:     u64
+	}
+
+	// proposer gives its nfts for nfts of partner with the unsigned transaction, whose nft output is taken by partner
+	#[derive(Serialize, Deserialize)]
+	pub struct ProposeSwap {
+		pub tx:        TransactionView,
+		pub nft_index: u32,
+		pub proposer:  [u8; 20],
+		pub gives:     Vec<String>,
+		pub takes:     Vec<String>
+	}
+
+	// seller or proposer submits the trade transaction signed by both sides
+	#[derive(Serialize, Deserialize)]
+	pub struct SubmitTrade {
+		pub tx: TransactionView
+	}
 }
 
 // response messages
@@ -109,4 +135,16 @@ pub mod response {
 		pub message:    String,
 		pub parameters: HashMap<String, GodotType>
 	}
+
+	// buyer or partner completes the trade transaction with its cells and signs its part
+	#[derive(Serialize, Deserialize)]
+	pub struct CompleteAndSignTrade {
+		pub tx: TransactionView
+	}
+
+	// response the result of checking confirmation status of trade transaction on CKB
+	#[derive(Serialize, Deserialize)]
+	pub struct SettleTrade {
+		pub result: bool
+	}
 }
@@ -27,11 +27,17 @@ where
 		.register("sync_operation", reply::sync_operation)
 		.register("sync_p2p_message", reply::sync_p2p_message)
 		.register("notify_game_over", reply::notify_game_over)
+		.register("offer_trade", reply::offer_trade)
+		.register("submit_trade", reply::submit_trade)
+		.register("propose_swap", reply::propose_swap)
 		.register_call("close_kabletop_channel")
 		.register_call("switch_round")
 		.register_call("sync_operation")
 		.register_call("sync_p2p_message")
 		.register_call("notify_game_over")
+		.register_call("offer_trade")
+		.register_call("submit_trade")
+		.register_call("propose_swap")
 		.listen(100, 1, callback);
 	match server {
 		Ok(listening) => {
@@ -50,9 +56,9 @@ pub fn change_client(client_id: i32) {
 	SERVER.lock().unwrap().as_mut().unwrap().set_id(client_id);
 }
 
-pub fn close_kabletop_channel() -> Result<[u8; 32], String> {
+pub fn close_kabletop_channel(fee_rate: Option<u64>) -> Result<[u8; 32], String> {
 	send::close_kabletop_channel(
-		SERVER.lock().unwrap().as_ref().unwrap()
+		SERVER.lock().unwrap().as_ref().unwrap(), fee_rate
 	)
 }
 
@@ -81,3 +87,15 @@ pub fn sync_p2p_message(
 		SERVER.lock().unwrap().as_ref().unwrap(), message, parameters
 	)
 }
+
+pub fn offer_trade(nfts: Vec<String>, price: u64) -> Result<[u8; 32], String> {
+	send::offer_trade(
+		SERVER.lock().unwrap().as_ref().unwrap(), nfts, price
+	)
+}
+
+pub fn propose_swap(gives: Vec<String>, takes: Vec<String>) -> Result<[u8; 32], String> {
+	send::propose_swap(
+		SERVER.lock().unwrap().as_ref().unwrap(), gives, takes
+	)
+}
\ No newline at end of file
@@ -0,0 +1,173 @@
+mod common;
+
+use common::{
+	Harness, Side
+};
+use kabletop_godot_sdk::{
+	cache, ckb, p2p::{
+		client, server
+	}
+};
+use futures::executor::block_on;
+use std::{
+	sync::mpsc, time::Duration
+};
+
+const CLIENT_NFTS: [[u8; 20]; 2] = [[1u8; 20], [2u8; 20]];
+const SERVER_NFTS: [[u8; 20]; 2] = [[3u8; 20], [4u8; 20]];
+
+// wait for the callback of one asynchronous ckb submission
+fn wait_submission<F>(submit: F) -> Result<ckb::H256, String>
+where
+	F: FnOnce(Box<dyn Fn(Result<ckb::H256, String>) + Send>)
+{
+	let (sender, receiver) = mpsc::channel();
+	submit(Box::new(move |result| {
+		let _ = sender.send(result);
+	}));
+	receiver
+		.recv_timeout(Duration::from_secs(300))
+		.map_err(|err| err.to_string())?
+}
+
+fn play_rounds(harness: &Harness, count: usize) {
+	for i in 0..count {
+		let side = if i % 2 == 0 { Side::Client } else { Side::Server };
+		harness
+			.play_round(side, &[&format!("spell({})", i), &format!("attack({})", i)])
+			.expect("play round");
+	}
+}
+
+fn finish_game(harness: &Harness, winner: u8) {
+	for side in vec![Side::Client, Side::Server] {
+		harness.as_side(side, || cache::set_winner(winner));
+	}
+	let owner = match harness.channel(Side::Client).round_owner {
+		1 => Side::Client,
+		_ => Side::Server
+	};
+	harness.as_side(owner, || {
+		match owner {
+			Side::Client => client::sync_operation(String::from("game_over()"))?,
+			Side::Server => server::sync_operation(String::from("game_over()"))?
+		}
+		match owner {
+			Side::Client => client::notify_game_over(),
+			Side::Server => server::notify_game_over()
+		}
+	}).expect("notify game over");
+}
+
+// fund both peers on mock chain and open a channel between them
+fn open_channel(harness: &Harness) -> [u8; 32] {
+	harness.fund(&CLIENT_NFTS, &SERVER_NFTS);
+	harness
+		.open_channel(600, 300, &CLIENT_NFTS, &SERVER_NFTS)
+		.expect("open kabletop channel")
+}
+
+#[test]
+fn rounds_and_game_over_on_mock_chain() {
+	let harness = Harness::start(11601);
+	open_channel(&harness);
+	play_rounds(&harness, 5);
+	harness.assert_rounds_signed();
+
+	let client = harness.channel(Side::Client);
+	let server = harness.channel(Side::Server);
+	assert_eq!(client.round_index, 6);
+	assert_eq!(client.round_owner, server.round_owner);
+	assert_eq!(client.opponent_nfts, SERVER_NFTS.to_vec());
+	assert_eq!(server.opponent_nfts, CLIENT_NFTS.to_vec());
+	assert_eq!(client.opponent_pkhash, server.user_pkhash);
+	assert!(client.round_operations.is_empty() && server.round_operations.is_empty());
+
+	finish_game(&harness, 1);
+	harness.assert_rounds_signed();
+	assert_eq!(harness.channel(Side::Server).winner, 1);
+	assert_eq!(harness.channel(Side::Client).round_index, 7);
+}
+
+#[test]
+fn opponent_disconnects_mid_round() {
+	let mut harness = Harness::start(11602);
+	open_channel(&harness);
+	play_rounds(&harness, 2);
+	harness.as_side(Side::Client, || client::sync_operation(String::from("spell(2)"))).expect("sync operation");
+	harness.disconnect_server();
+
+	let result = harness.as_side(Side::Client, || client::sync_operation(String::from("attack(2)")));
+	assert!(result.is_err(), "operation is synced to a disconnected opponent");
+	assert!(harness.as_side(Side::Client, client::switch_round).is_err(), "round is switched without opponent");
+
+	// the unfinished round stays with the client, which is what challenge_channel needs
+	let client = harness.channel(Side::Client);
+	assert_eq!(client.signed_rounds.len(), 2);
+	assert_eq!(client.round_owner, client.user_type);
+	assert_eq!(client.round_operations.first().map(String::as_str), Some("spell(2)"));
+	assert_eq!(harness.channel(Side::Server).round_operations, vec![String::from("spell(2)")]);
+}
+
+#[test]
+fn mismatched_operations_are_refused() {
+	let harness = Harness::start(11603);
+	open_channel(&harness);
+	play_rounds(&harness, 1);
+	harness.as_side(Side::Server, || server::sync_operation(String::from("spell(1)"))).expect("sync operation");
+
+	// client records an operation which never reaches the server
+	harness.as_side(Side::Client, || cache::commit_opponent_operation(String::from("forged(1)"))).expect("commit operation");
+	assert!(harness.as_side(Side::Server, server::switch_round).is_err(), "mismatched round is signed");
+	assert!(harness.as_side(Side::Server, server::notify_game_over).is_err(), "mismatched game over is signed");
+
+	let client = harness.channel(Side::Client);
+	let server = harness.channel(Side::Server);
+	assert_eq!(client.signed_rounds.len(), 1);
+	assert_eq!(server.signed_rounds.len(), 1);
+	assert_ne!(client.round_operations, server.round_operations);
+
+	// and the round can't be switched by the opponent of its owner
+	assert!(harness.as_side(Side::Client, client::switch_round).is_err(), "round is switched by its opponent");
+}
+
+#[test]
+fn full_lifecycle_on_mock_chain() {
+	let harness = Harness::start(11604);
+	let channel_hash = open_channel(&harness);
+	assert_eq!(harness.channel(Side::Client).channel_hash, channel_hash);
+	assert_eq!(harness.channel(Side::Server).channel_hash, channel_hash);
+	assert_eq!(harness.channel(Side::Client).script_hash, harness.channel(Side::Server).script_hash);
+
+	play_rounds(&harness, 4);
+	finish_game(&harness, 1);
+	harness.assert_rounds_signed();
+
+	harness.as_side(Side::Client, || client::close_kabletop_channel(None)).expect("close kabletop channel");
+	let script_args = harness.channel(Side::Client).script_args;
+	let (live, _) = block_on(ckb::get_kabletop_challenge_data(script_args)).expect("challenge data");
+	assert!(!live, "kabletop cell is still alive after closing");
+}
+
+#[test]
+fn challenge_and_close_from_challenge_on_mock_chain() {
+	let mut harness = Harness::start(11605);
+	open_channel(&harness);
+	play_rounds(&harness, 2);
+	harness.as_side(Side::Client, || client::sync_operation(String::from("spell(2)"))).expect("sync operation");
+	harness.disconnect_server();
+
+	let store = harness.channel(Side::Client);
+	let signed_rounds = store.kabletop_signed_rounds().expect("signed rounds");
+	wait_submission(|f| harness.as_side(Side::Client, || ckb::challenge_kabletop_channel(
+		store.script_args.clone(), store.user_type, store.round_operations.clone(), signed_rounds.clone(), None, f
+	))).expect("challenge kabletop channel");
+	let (live, challenge) = block_on(ckb::get_kabletop_challenge_data(store.script_args.clone())).expect("challenge data");
+	assert!(live && challenge.is_some(), "kabletop cell isn't in challenge");
+
+	wait_submission(|f| harness.as_side(Side::Client, || ckb::close_challenged_kabletop_channel(
+		store.script_args.clone(), store.user_type, true, signed_rounds, None, f
+	))).expect("close challenged kabletop channel");
+	let (live, _) = block_on(ckb::get_kabletop_challenge_data(store.script_args)).expect("challenge data");
+	assert!(!live, "kabletop cell is still alive after closing from challenge");
+}
\ No newline at end of file
@@ -0,0 +1,242 @@
+#![allow(dead_code)]
+
+use kabletop_godot_sdk::{
+	cache, account, ckb::{
+		self, mock
+	}, p2p::{
+		client, server, protocol::methods::reply
+	}
+};
+use kabletop_ckb_sdk::{
+	config::VARS, ckb::transaction::channel::interact as channel
+};
+use std::{
+	path::PathBuf, time::Duration, sync::{
+		Mutex, MutexGuard, mpsc
+	}
+};
+
+const PASSWORD: &str = "kabletop-harness";
+
+// the server plays with this key, which is the publicly known key of the first genesis account in ckb's dev chain
+// spec, so it never guards anything of value, and the mock chain funds it from thin air anyway
+const SERVER_PRIVKEY: &str = "d00c06bfd800d27397002dca6fb0993d5ba6399b4238b2f29ee9deb97593d2bc";
+
+// capacity issued to each account, which covers staking, nft cells and fee of every test
+const FUNDING_CAPACITY: u64 = 500_000_000_000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Side {
+	Client, Server
+}
+
+impl Side {
+	pub fn opposite(self) -> Side {
+		match self {
+			Side::Client => Side::Server,
+			Side::Server => Side::Client
+		}
+	}
+}
+
+// the native states of one peer, which are swapped into sdk globals while the peer is acting
+struct Peer {
+	pkhash:  Option<[u8; 20]>,
+	channel: cache::ChannelCache
+}
+
+struct Context {
+	acting: Side,
+	client: Peer,
+	server: Peer
+}
+
+impl Context {
+	fn peer(&mut self, side: Side) -> &mut Peer {
+		match side {
+			Side::Client => &mut self.client,
+			Side::Server => &mut self.server
+		}
+	}
+}
+
+lazy_static::lazy_static! {
+	// peers share sdk globals, so harnesses must run one after another
+	static ref SERIAL:  Mutex<()>              = Mutex::new(());
+	static ref CONTEXT: Mutex<Option<Context>> = Mutex::new(None);
+}
+
+fn acting() -> Side {
+	CONTEXT.lock().unwrap().as_ref().expect("harness isn't running").acting
+}
+
+// stash the states of acting peer and bring the states of specified peer into sdk globals
+fn activate(side: Side) {
+	let mut context = CONTEXT.lock().unwrap();
+	let context = context.as_mut().expect("harness isn't running");
+	if context.acting == side {
+		return
+	}
+	let peer = context.peer(side);
+	let (pkhash, channel) = (peer.pkhash, peer.channel.clone());
+	let acting = context.acting;
+	context.peer(acting).channel = cache::replace(channel);
+	account::switch_account(pkhash, PASSWORD).expect("switch account");
+	context.acting = side;
+}
+
+// a server and a client connected in one process, replies of each side run with its own cache and account
+pub struct Harness {
+	_serial:      MutexGuard<'static, ()>,
+	server_alive: bool,
+	origin_dir:   PathBuf,
+	work_dir:     PathBuf
+}
+
+impl Harness {
+	pub fn start(port: u16) -> Harness {
+		let serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+		// keystore and db of the sdk are relative to working directory, so each harness runs in a fresh temporary one
+		// to keep accounts and caches of the developer untouched, the config is loaded before leaving the crate
+		let _ = &VARS.common;
+		let origin_dir = std::env::current_dir().expect("current dir");
+		let work_dir = std::env::temp_dir().join(format!("kabletop-harness-{}-{}", std::process::id(), port));
+		let _ = std::fs::remove_dir_all(&work_dir);
+		std::fs::create_dir_all(&work_dir).expect("create harness dir");
+		std::env::set_current_dir(&work_dir).expect("enter harness dir");
+
+		mock::set_mock_chain(true).expect("serve mock chain");
+		mock::reset_mock_chain();
+		mock::set_mock_auto_mine(true);
+		ckb::set_confirmation_depth(1);
+
+		// the client plays with the configured account, and the server with a keystore one
+		let server_pkhash = account::import_account(String::from("harness"), SERVER_PRIVKEY, PASSWORD).expect("import account");
+		account::switch_account(Some(server_pkhash), PASSWORD).expect("switch account");
+		cache::init(cache::PLAYER_TYPE::TWO);
+		let server_channel = cache::get_clone();
+		account::switch_account(None, PASSWORD).expect("switch account");
+		cache::init(cache::PLAYER_TYPE::ONE);
+		*CONTEXT.lock().unwrap() = Some(Context {
+			acting: Side::Client,
+			client: Peer {
+				pkhash:  None,
+				channel: cache::get_clone()
+			},
+			server: Peer {
+				pkhash:  Some(server_pkhash),
+				channel: server_channel
+			}
+		});
+
+		// every call is answered by the opposite of the acting peer, and the caller is restored afterwards
+		reply::scope::set(
+			|| activate(acting().opposite()),
+			|| activate(acting().opposite())
+		);
+
+		let (sender, receiver) = mpsc::channel();
+		let sender = Mutex::new(sender);
+		server::listen(format!("127.0.0.1:{}", port).as_str(), move |id, connected| {
+			if connected {
+				server::change_client(id);
+				let _ = sender.lock().unwrap().send(());
+			}
+		}).expect("server listen");
+		client::connect(format!("ws://127.0.0.1:{}", port).as_str(), || {}).expect("client connect");
+		receiver.recv_timeout(Duration::from_secs(10)).expect("server never saw the client");
+		Harness {
+			_serial:      serial,
+			server_alive: true,
+			origin_dir,
+			work_dir
+		}
+	}
+
+	// run f with the states of specified peer
+	pub fn as_side<R, F: FnOnce() -> R>(&self, side: Side, f: F) -> R {
+		activate(side);
+		f()
+	}
+
+	pub fn channel(&self, side: Side) -> cache::ChannelCache {
+		self.as_side(side, cache::get_clone)
+	}
+
+	// fund both accounts on mock chain, and give each of them the nfts it's going to play
+	pub fn fund(&self, client_nfts: &[[u8; 20]], server_nfts: &[[u8; 20]]) {
+		for (side, nfts) in vec![(Side::Client, client_nfts), (Side::Server, server_nfts)] {
+			let pkhash = self.channel(side).user_pkhash;
+			mock::issue_mock_capacity(&pkhash, FUNDING_CAPACITY);
+			mock::issue_mock_nfts(&pkhash, nfts).expect("issue nfts");
+		}
+	}
+
+	// open a kabletop channel from the client, both peers stake staking_ckb and play the specified nfts
+	pub fn open_channel(&self, staking_ckb: u64, bet_ckb: u64, client_nfts: &[[u8; 20]], server_nfts: &[[u8; 20]]) -> Result<[u8; 32], String> {
+		for (side, nfts) in vec![(Side::Client, client_nfts), (Side::Server, server_nfts)] {
+			self.as_side(side, || {
+				cache::set_staking_and_bet_ckb(staking_ckb, bet_ckb);
+				cache::set_playing_nfts(nfts.to_vec());
+			});
+		}
+		self.as_side(Side::Client, client::open_kabletop_channel)
+	}
+
+	// play one round from round owner, which syncs operations and then switchs round to the opposite
+	pub fn play_round(&self, side: Side, operations: &[&str]) -> Result<[u8; 65], String> {
+		self.as_side(side, || {
+			for operation in operations {
+				match side {
+					Side::Client => client::sync_operation(operation.to_string())?,
+					Side::Server => server::sync_operation(operation.to_string())?
+				}
+			}
+			match side {
+				Side::Client => client::switch_round(),
+				Side::Server => server::switch_round()
+			}
+		})
+	}
+
+	pub fn disconnect_server(&mut self) {
+		if self.server_alive {
+			server::disconnect();
+			self.server_alive = false;
+		}
+	}
+
+	// both peers must have the same signed rounds, and each of them is signed by the peer who doesn't own it
+	pub fn assert_rounds_signed(&self) {
+		let client = self.channel(Side::Client);
+		let server = self.channel(Side::Server);
+		assert_eq!(client.signed_rounds, server.signed_rounds);
+		assert_eq!(client.round_index, server.round_index);
+		assert_eq!(client.round_index, client.signed_rounds.len() + 1);
+		let rounds = client.kabletop_signed_rounds().expect("decode signed rounds");
+		for i in 1..=rounds.len() {
+			let signer = match u8::from(rounds[i - 1].0.user_type()) {
+				1 => server.user_pkhash,
+				_ => client.user_pkhash
+			};
+			let ok = channel::check_channel_round(client.script_hash.into(), rounds[..i].to_vec(), signer)
+				.expect("check channel round");
+			assert!(ok, "signature of round {} isn't signed by {}", i, hex::encode(signer));
+		}
+	}
+}
+
+impl Drop for Harness {
+	fn drop(&mut self) {
+		reply::scope::clear();
+		client::disconnect();
+		self.disconnect_server();
+		activate(Side::Client);
+		cache::clear();
+		*CONTEXT.lock().unwrap() = None;
+		let _ = mock::set_mock_chain(false);
+		let _ = std::env::set_current_dir(&self.origin_dir);
+		let _ = std::fs::remove_dir_all(&self.work_dir);
+	}
+}
\ No newline at end of file
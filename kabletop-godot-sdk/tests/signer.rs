@@ -0,0 +1,189 @@
+use kabletop_godot_sdk::{
+	cache, ckb::{
+		mock, RemoteSigner, Signer, KeySigner, ApprovalPolicy
+	}
+};
+use kabletop_ckb_sdk::{
+	config::VARS, ckb::transaction::channel::interact as channel
+};
+use ckb_types::{
+	H256, core::TransactionView, prelude::*
+};
+use futures::executor::block_on;
+use zeroize::Zeroizing;
+use std::{
+	thread, io::Write, time::Duration, process::{
+		Command, Child, Stdio
+	}
+};
+
+const PRIVKEY: &str = "d00c06bfd800d27397002dca6fb0993d5ba6399b4238b2f29ee9deb97593d2bc";
+
+const TOKEN: &str = "standin-token";
+
+const CKB_SHANNONS: u64 = 100_000_000;
+
+// the stand-in companion process, which is killed once dropped
+struct Companion {
+	process: Child,
+	address: String
+}
+
+impl Companion {
+	fn spawn(port: u16, args: &[&str]) -> Self {
+		Companion::answering(port, args, "")
+	}
+
+	// answers are typed on terminal of companion in order, and everything asked after them is declined
+	fn answering(port: u16, args: &[&str], answers: &str) -> Self {
+		let address = format!("127.0.0.1:{}", port);
+		let mut process = Command::new(env!("CARGO_BIN_EXE_standin_signer"))
+			.args(&["--listen", address.as_str(), "--token", TOKEN, "--privkey", PRIVKEY])
+			.args(args)
+			.env_remove("KABLETOP_MOCK_CHAIN")
+			.stdin(Stdio::piped())
+			.stdout(Stdio::null())
+			.spawn()
+			.expect("spawn standin_signer");
+		let mut stdin = process.stdin.take().unwrap();
+		stdin.write_all(answers.as_bytes()).expect("answer standin_signer");
+		drop(stdin);
+		Companion {
+			process,
+			address
+		}
+	}
+
+	// companion takes a while to listen after spawned
+	fn connect(&self) -> RemoteSigner {
+		for _ in 0..50 {
+			if let Ok(signer) = RemoteSigner::connect(self.address.as_str(), TOKEN) {
+				return signer
+			}
+			thread::sleep(Duration::from_millis(100));
+		}
+		panic!("can't connect standin_signer at {}", self.address);
+	}
+}
+
+impl Drop for Companion {
+	fn drop(&mut self) {
+		let _ = self.process.kill();
+		let _ = self.process.wait();
+	}
+}
+
+// a channel between the configured account and the companion's, staking staking_ckb on each side, the companion
+// reaches the mock chain served by this process through the configured ckb_uri
+fn prepare_channel(staking_ckb: u64) -> TransactionView {
+	let companion = local_signer().pubhash();
+	let user_nfts = vec![[1u8; 20], [2u8; 20]];
+	let companion_nfts = vec![[3u8; 20], [4u8; 20]];
+	mock::set_mock_chain(true).expect("serve mock chain");
+	mock::reset_mock_chain();
+	for (pkhash, nfts) in vec![(VARS.common.user_key.pubhash, &user_nfts), (companion, &companion_nfts)] {
+		mock::issue_mock_capacity(&pkhash, 500_000_000_000);
+		mock::issue_mock_nfts(&pkhash, nfts).expect("issue nfts");
+	}
+	let hashes = VARS.luacodes.iter().map(|value| value.data_hash.clone()).collect::<Vec<_>>();
+	let staking = staking_ckb * CKB_SHANNONS;
+	let tx = block_on(channel::prepare_channel_tx(
+		staking, 0, 40, user_nfts, VARS.common.user_key.pubhash, hashes.clone()
+	)).expect("prepare channel tx");
+	block_on(channel::complete_channel_tx(
+		tx.into(), staking, 0, 40, companion_nfts, companion, hashes
+	)).expect("complete channel tx")
+}
+
+fn local_signer() -> KeySigner {
+	let mut secret = Zeroizing::new([0u8; 32]);
+	hex::decode_to_slice(PRIVKEY, &mut secret[..]).unwrap();
+	KeySigner::from_secret(secret).unwrap()
+}
+
+#[test]
+fn remote_signer_signs_with_companion_key() {
+	let companion = Companion::spawn(50811, &["--approve-all"]);
+	let signer = companion.connect();
+	let local = local_signer();
+	assert_eq!(signer.pubhash(), local.pubhash());
+
+	let message = H256::from([7u8; 32]);
+	let signature = signer.sign_message(&message).expect("sign message");
+	assert_eq!(signature.serialize(), local.sign_message(&message).unwrap().serialize());
+
+	let round = channel::make_round(1, vec![String::from("spell(1)")]);
+	let signature = signer.sign_round([9u8; 32].pack(), vec![], round.clone()).expect("sign round");
+	assert_eq!(signature.serialize(), local.sign_round([9u8; 32].pack(), vec![], round).unwrap().serialize());
+}
+
+#[test]
+fn round_digest_matches_sdk_signing() {
+	let signer = local_signer();
+	let script_hash = [9u8; 32];
+	let mut signed_rounds = vec![];
+	let mut last = script_hash.to_vec();
+	for i in 0..3u8 {
+		let round = channel::make_round(i % 2 + 1, vec![format!("spell({})", i)]);
+		let signature = signer.sign_round(script_hash.pack(), signed_rounds.clone(), round.clone()).expect("sign round");
+		assert_eq!(cache::recover_round_signer(&last, &round, &signature), Some(signer.pubhash()));
+		last = signature.serialize();
+		signed_rounds.push((round, signature));
+	}
+}
+
+#[test]
+fn unapproved_signings_are_declined() {
+	let companion = Companion::spawn(50812, &["--auto-round", "1000"]);
+	let signer = companion.connect();
+	let declined = Err(String::from("signing is declined by companion"));
+	assert_eq!(signer.sign_message(&H256::from([7u8; 32])).map(|signature| signature.serialize()), declined);
+
+	// rounds of a channel which companion hasn't signed are out of any policy
+	let round = channel::make_round(1, vec![String::from("spell(1)")]);
+	assert_eq!(signer.sign_round([9u8; 32].pack(), vec![], round).map(|signature| signature.serialize()), declined);
+	let policy = ApprovalPolicy {
+		channel:         None,
+		max_staking_ckb: 2000
+	};
+	assert_eq!(signer.request_auto_approval(policy), Err(String::from("signing is declined by companion")));
+}
+
+#[test]
+fn connection_without_token_is_refused() {
+	let companion = Companion::spawn(50813, &["--approve-all"]);
+	drop(companion.connect());
+	let refused = RemoteSigner::connect(companion.address.as_str(), "guessed-token").map(|signer| signer.pubhash());
+	assert_eq!(refused, Err(String::from("invalid companion token")));
+}
+
+#[test]
+fn auto_approval_signs_rounds_within_staking_limit() {
+	let tx = prepare_channel(600);
+	let script_hash = tx.output(0).unwrap().calc_lock_hash();
+	let round = channel::make_round(1, vec![String::from("spell(1)")]);
+	let companion_nfts = vec![[3u8; 20], [4u8; 20]];
+
+	// staking of channel is 600 ckb in lock args, which a policy of 1000 ckb covers but one of 500 ckb doesn't, and
+	// only the channel is approved on terminal, so a round is signed only if the policy covers it
+	for (port, limit, covered) in vec![(50814, "1000", true), (50815, "500", false)] {
+		let companion = Companion::answering(port, &["--auto-round", limit], "y\n");
+		let signer = companion.connect();
+
+		// game can't make companion believe a smaller staking than the channel really has
+		let misreported = signer.sign_channel_tx(tx.clone(), 100 * CKB_SHANNONS, 0, 40, companion_nfts.clone());
+		assert_eq!(misreported.map(|_| ()), Err(String::from("channel tx mismatches the staking, deck size or nfts reported by game")));
+		signer
+			.sign_channel_tx(tx.clone(), 600 * CKB_SHANNONS, 0, 40, companion_nfts.clone())
+			.expect("sign channel tx");
+
+		let signature = signer.sign_round(script_hash.clone(), vec![], round.clone()).map(|signature| signature.serialize());
+		if covered {
+			let expected = local_signer().sign_round(script_hash.clone(), vec![], round.clone()).unwrap().serialize();
+			assert_eq!(signature, Ok(expected));
+		} else {
+			assert_eq!(signature, Err(String::from("signing is declined by companion")));
+		}
+	}
+	let _ = mock::set_mock_chain(false);
+}
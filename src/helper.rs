@@ -4,20 +4,18 @@ use futures::executor::block_on;
 use molecule::prelude::Entity;
 use ckb_crypto::secp::Signature;
 use kabletop_godot_sdk::{
-	lua::highlevel::Lua, cache, lua, ckb::*, p2p::{
+	lua::highlevel::Lua, cache, lua, account, catalog, deck, ckb::*, p2p::{
 		client, server, protocol::types::GodotType, protocol_relay::types::ClientInfo
 	}
 };
-use kabletop_ckb_sdk::{
-	config::VARS, ckb::{
-		rpc::methods::get_tip_block_number, transaction::{
-			helper::*, channel::{
-				interact as channel, protocol::{
-					Challenge, Round
-				}
+use kabletop_ckb_sdk::ckb::{
+	transaction::{
+		helper::*, channel::{
+			interact as channel, protocol::{
+				Challenge, Round, Args
 			}
-		} 
-	}
+		}
+	} 
 };
 use std::{
 	sync::Mutex, thread, convert::TryInto, collections::HashMap
@@ -36,9 +34,8 @@ lazy_static::lazy_static! {
 	pub static ref CODES:    Mutex<Vec<(String, bool)>>               = Mutex::new(vec![]);
 	pub static ref LUA:      Mutex<Option<Lua>>                       = Mutex::new(None);
 	pub static ref NFTS:     Mutex<Option<Variant>>                   = Mutex::new(None);
-	pub static ref STATUS:   Mutex<Option<(u8, bool)>>                = Mutex::new(None);
+	pub static ref STATUS:   Mutex<Option<WalletStatus>>              = Mutex::new(None);
 	pub static ref P2PMODE:  Mutex<P2pMode>                           = Mutex::new(P2pMode::Empty);
-	pub static ref DELAIES:  Mutex<HashMap<String, Vec<(f32, Box<dyn Fn() + 'static + Send + Sync>)>>> = Mutex::new(HashMap::new());
 }
 
 pub fn set_godot_emitor(godot_node: Ref<Node>) {
@@ -163,65 +160,161 @@ where
 	})
 }
 
-pub fn update_owned_nfts() {
-	thread::spawn(|| {
-		let nfts = {
+pub fn into_wallet_action(action: String, params: Dictionary) -> Result<WalletAction, String> {
+	let nfts = || from_dictionary(params.get("nfts").to_dictionary());
+	match action.as_str() {
+		"discard"        => Ok(WalletAction::Discard(nfts())),
+		"transfer"       => Ok(WalletAction::Transfer(nfts(), params.get("to").to_string())),
+		"transfer_batch" => Ok(WalletAction::TransferBatch(from_transfers_dictionary(params.get("transfers").to_dictionary()))),
+		"issue"          => Ok(WalletAction::Issue(nfts())),
+		"purchase"       => Ok(WalletAction::Purchase(params.get("count").to_u64() as u8)),
+		"reveal"         => Ok(WalletAction::Reveal),
+		"create_wallet"  => Ok(WalletAction::CreateWallet),
+		_                => Err(format!("unknown wallet action {}", action))
+	}
+}
+
+pub fn from_transaction_summary(summary: TransactionSummary) -> Dictionary<Unique> {
+	let into_cells = |cells: Vec<CellSummary>| cells
+		.into_iter()
+		.map(|cell| {
+			let value = Dictionary::new();
+			value.insert("capacity", cell.capacity);
+			value.insert("lock_hash", hex::encode(cell.lock_hash));
+			value.insert("is_user", cell.is_user);
+			value.insert("nfts", cell.nfts);
+			value.into_shared()
+		})
+		.collect::<Vec<_>>();
+	let value = Dictionary::new();
+	value.insert("tx_hash", hex::encode(summary.tx_hash));
+	value.insert("inputs", into_cells(summary.inputs));
+	value.insert("outputs", into_cells(summary.outputs));
+	value.insert("nfts_received", summary.nfts_received);
+	value.insert("nfts_sent", summary.nfts_sent);
+	value.insert("capacity_change", summary.capacity_change);
+	value.insert("fee", summary.fee);
+	value
+}
+
+pub fn get_transaction_history(limit: usize, cursor: String) -> Result<Dictionary, String> {
+	let cursor = if cursor.is_empty() {
+		None
+	} else {
+		Some(cursor)
+	};
+	let (records, cursor) = transaction_history(limit, cursor)?;
+	let records = records
+		.into_iter()
+		.map(|record| {
+			let value = from_transaction_summary(record.summary);
+			value.insert("block_number", record.block_number);
+			value.insert("kind", record.kind.name());
+			value.into_shared()
+		})
+		.collect::<Vec<_>>();
+	let value = Dictionary::new();
+	value.insert("records", records);
+	value.insert("cursor", cursor.unwrap_or_default());
+	Ok(value.into_shared())
+}
+
+pub fn from_nft_info(info: catalog::NftInfo) -> Dictionary {
+	let attributes = Dictionary::new();
+	for (key, value) in info.attributes {
+		attributes.insert(key, value);
+	}
+	let value = Dictionary::new();
+	value.insert("hash", info.hash);
+	value.insert("name", info.name);
+	value.insert("rarity", info.rarity);
+	value.insert("image", info.image);
+	value.insert("attributes", attributes.into_shared());
+	value.insert("composer_matches", info.composer_matches);
+	value.into_shared()
+}
+
+pub fn from_owned_nfts(owned_nfts: &HashMap<String, u32>) -> Dictionary<Unique> {
+	let nfts = Dictionary::new();
+	for (nft, count) in owned_nfts {
+		nfts.insert(nft.clone(), count.to_variant());
+	}
+	nfts
+}
+
+// forward changes found by chain watcher to godot signals, and keep the latest nfts and box status for cache reading
+pub fn handle_chain_event(event: ChainEvent) {
+	match event {
+		ChainEvent::OwnedNfts(owned_nfts) => {
 			let nfts = Dictionary::new();
-			match owned_nfts() {
-				Ok(owned_nfts) => {
-					for (nft, count) in owned_nfts {
-						nfts.insert(nft, count.to_variant());
-					}
-					Some(nfts.into_shared().to_variant())
-				},
-				Err(err) => {
-					godot_print!("update_owned_nfts error: {}", err);
-					None
+			let infos = Dictionary::new();
+			for (nft, count) in owned_nfts {
+				if let Some(info) = catalog::nft_info(nft.as_str()) {
+					infos.insert(nft.clone(), from_nft_info(info));
 				}
+				nfts.insert(nft, count.to_variant());
 			}
-		};
-		if *NFTS.lock().unwrap() != nfts {
-			*NFTS.lock().unwrap() = nfts.clone();
-			DELAIES.lock().unwrap().remove(&String::from("update_owned_nfts"));
-			push_event("owned_nfts_updated", vec![nfts.unwrap_or_default()]);
-		} else {
-			DELAIES.lock().unwrap().insert(String::from("update_owned_nfts"), vec![
-				(2.0, Box::new(update_owned_nfts)), (2.0, Box::new(update_owned_nfts)), (2.0, Box::new(update_owned_nfts))
-			]);
+			let nfts = nfts.into_shared().to_variant();
+			*NFTS.lock().unwrap() = Some(nfts.clone());
+			push_event("owned_nfts_updated", vec![nfts, infos.into_shared().to_variant()]);
+		},
+		ChainEvent::WalletStatus(status) => {
+			let value = from_wallet_status(&status).into_shared().to_variant();
+			*STATUS.lock().unwrap() = Some(status);
+			push_event("box_status_updated", vec![value]);
+		},
+		ChainEvent::Capacity(capacity) => {
+			push_event("capacity_updated", vec![capacity.to_variant()]);
+		},
+		ChainEvent::ChannelCell(alive, challenger) => {
+			push_event("channel_cell_updated", vec![alive.to_variant(), challenger.unwrap_or(0).to_variant()]);
 		}
-	});
+	}
 }
 
-pub fn update_box_status() {
-	thread::spawn(|| {
-		let mut status = (0, true);
-		match wallet_status() {
-			Ok((count, ready)) => status = (count, ready),
-			Err(err)           => godot_print!("update_box_status error: {}", err)
-		}
-		if *STATUS.lock().unwrap() != Some(status) {
-			*STATUS.lock().unwrap() = Some(status);
-			DELAIES.lock().unwrap().remove(&String::from("update_box_status"));
-			push_event("box_status_updated", vec![status.0.to_variant(), status.1.to_variant()]);
-		} else {
-			DELAIES.lock().unwrap().insert(String::from("update_box_status"), vec![
-				(2.0, Box::new(update_box_status)), (2.0, Box::new(update_box_status)), (2.0, Box::new(update_box_status))
-			]);
-		}
-	});
+pub fn from_wallet_status(status: &WalletStatus) -> Dictionary<Unique> {
+	let value = Dictionary::new();
+	value.insert("mode", status.mode.name());
+	value.insert("exists", status.mode != WalletMode::Absent);
+	value.insert("pending_boxes", status.pending_boxes);
+	value.insert("box_price", status.box_price);
+	value.insert("reveal_block", status.reveal_block.map_or(Variant::default(), |block| block.to_variant()));
+	value.insert("pool_capacity", status.pool_capacity);
+	value
 }
 
-pub fn process_delay_funcs(delta_sec: f32) {
-	if let Ok(mut delaies) = DELAIES.try_lock() {
-		for (_, funcs) in &mut *delaies {
-			if let Some((delay, func)) = funcs.get_mut(0) {
-				*delay -= delta_sec;
-				if *delay < 0.0 {
-					func();
-				}
-				drop(funcs.remove(0));
-			}
-		}
+pub fn from_balance(balance: &Balance) -> Dictionary<Unique> {
+	let value = Dictionary::new();
+	value.insert("total", balance.total);
+	value.insert("free", balance.free);
+	value.insert("spendable", balance.spendable);
+	value.insert("nft_occupied", balance.nft_occupied);
+	value.insert("other_occupied", balance.other_occupied);
+	value.insert("wallet", balance.wallet);
+	value.insert("staked", balance.staked);
+	value.insert("pending_incoming", balance.pending_incoming);
+	value.insert("pending_outgoing", balance.pending_outgoing);
+	value
+}
+
+pub fn from_consolidation_preview(preview: &ConsolidationPreview) -> Dictionary<Unique> {
+	let value = Dictionary::new();
+	value.insert("nft_cells_before", preview.nft_cells_before as u64);
+	value.insert("nft_cells_after", preview.nft_cells_after as u64);
+	value.insert("ckb_cells_before", preview.ckb_cells_before as u64);
+	value.insert("ckb_cells_after", preview.ckb_cells_after as u64);
+	value.insert("transactions", preview.transactions as u64);
+	value.insert("estimated_fee", preview.estimated_fee);
+	value
+}
+
+// validate selected nfts before opening channel, all violations are joined into one error message
+pub fn check_deck(nfts: &Vec<String>) -> Result<(), String> {
+	let issues = deck::validate_deck(nfts)?;
+	if issues.is_empty() {
+		Ok(())
+	} else {
+		Err(issues.join("; "))
 	}
 }
 
@@ -230,14 +323,78 @@ pub fn persist_kabletop_cache() {
 	cache::persist(filename).expect("persist");
 }
 
-pub fn remove_kabletop_cache(script_hash: String) -> bool {
-	let filename = format!("db/{}.json", script_hash);
-	if let Ok(_) = std::fs::remove_file(filename) {
-		true
-	} else {
-		println!("file {}.json not found", script_hash);
-		false
+pub fn archive_kabletop_cache(winner: u8, close_hash: [u8; 32]) -> bool {
+	match cache::archive(winner, close_hash) {
+		Ok(_)    => true,
+		Err(err) => {
+			println!("archive kabletop cache error: {}", err);
+			false
+		}
+	}
+}
+
+pub fn into_match_filter(value: Dictionary) -> Result<cache::MatchFilter, String> {
+	let mut filter = cache::MatchFilter::default();
+	if value.contains("opponent") {
+		let pkhash = blake160_to_byte20(value.get("opponent").to_string().as_str()).map_err(|err| err.to_string())?;
+		filter.opponent_pkhash = Some(pkhash);
 	}
+	if value.contains("result") {
+		match value.get("result").to_string().as_str() {
+			"win"  => filter.is_winner = Some(true),
+			"lose" => filter.is_winner = Some(false),
+			other  => return Err(format!("unknown match result {}", other))
+		}
+	}
+	if value.contains("since") {
+		filter.since = Some(value.get("since").to_u64());
+	}
+	if value.contains("until") {
+		filter.until = Some(value.get("until").to_u64());
+	}
+	if value.contains("limit") {
+		filter.limit = Some(value.get("limit").to_u64() as usize);
+	}
+	Ok(filter)
+}
+
+pub fn get_match_history(filter: cache::MatchFilter) -> Result<Vec<Dictionary>, String> {
+	let records = cache::match_history(filter)?
+		.into_iter()
+		.map(|record| {
+			let value = Dictionary::new();
+			value.insert("script_hash", hex::encode(record.script_hash));
+			value.insert("close_hash", hex::encode(record.close_hash));
+			value.insert("opponent_pkhash", hex::encode(record.opponent_pkhash));
+			value.insert("user_type", record.user_type);
+			value.insert("winner", record.winner);
+			value.insert("result", if record.is_unknown() { "unknown" } else if record.is_winner() { "win" } else { "lose" });
+			value.insert("ckb_delta", record.ckb_delta() / 100_000_000);
+			value.insert("staking_ckb", record.staking_ckb / 100_000_000);
+			value.insert("bet_ckb", record.bet_ckb / 100_000_000);
+			value.insert("round_count", record.round_count);
+			value.insert("duration", record.duration());
+			value.insert("open_timestamp", record.open_timestamp);
+			value.insert("close_timestamp", record.close_timestamp);
+			value.into_shared()
+		})
+		.collect::<Vec<_>>();
+	Ok(records)
+}
+
+pub fn verify_kabletop_cache(script_hash: String) -> Result<Dictionary, String> {
+	let report = cache::verify_cache(script_hash)?;
+	let value = Dictionary::new();
+	value.insert("valid", report.is_valid());
+	value.insert("script_hash_matched", report.script_hash_matched);
+	value.insert("pkhash_matched", report.pkhash_matched);
+	value.insert("round_count", report.round_count);
+	value.insert("invalid_signatures", report.invalid_signatures);
+	value.insert("invalid_operations", report.invalid_operations);
+	value.insert("channel_alive", report.channel_alive);
+	value.insert("challenger", report.challenger.unwrap_or(0));
+	value.insert("issues", report.issues);
+	Ok(value.into_shared())
 }
 
 pub fn complete_signed_rounds_for_challenge() -> Result<(Vec<(Round, Signature)>, bool), String> {
@@ -252,6 +409,8 @@ pub fn complete_signed_rounds_for_challenge() -> Result<(Vec<(Round, Signature)>
 					// found in current challenge data, so extract it and complete the rounds data
 					if !store.round_operations.is_empty() {
 						cache::commit_user_round(data.snapshot_signature().into());
+					} else {
+						cache::hand_round_to_opponent();
 					}
 					// make new round for pending operations of user who had been challenged
 					let opponent_operations = Vec::from(data.operations())
@@ -259,21 +418,15 @@ pub fn complete_signed_rounds_for_challenge() -> Result<(Vec<(Round, Signature)>
 						.map(|bytes| String::from_utf8(bytes).map_err(|e| e.to_string()))
 						.collect::<Result<Vec<_>, _>>()?
 						.into_iter()
-						.map(|operation| {
-							cache::commit_opponent_operation(operation.clone());
-							operation
-						})
-						.collect::<Vec<_>>();
+						.map(|operation| cache::commit_opponent_operation(operation.clone()).map(|_| operation))
+						.collect::<Result<Vec<_>, _>>()?;
 					if !opponent_operations.is_empty() {
 						let opponent_round = channel::make_round(store.opponent_type, opponent_operations);
 						let script_hash = kabletop_script(store.script_args).calc_script_hash();
-						let signature = channel::sign_channel_round(
-							script_hash, cache::get_kabletop_signed_rounds()?, opponent_round, &VARS.common.user_key.privkey
-						);
-						if let Err(error) = signature {
-							return Err(error.to_string())
-						}
-						cache::commit_opponent_round(signature.unwrap());
+						let signature = account::active_signer().sign_round(
+							script_hash, cache::get_kabletop_signed_rounds()?, opponent_round
+						)?;
+						cache::commit_opponent_round(signature);
 					}
 				}
 				challenging = true;
@@ -286,30 +439,31 @@ pub fn complete_signed_rounds_for_challenge() -> Result<(Vec<(Round, Signature)>
 }
 
 pub fn scan_uncomplete_kabletop_cache() -> Result<Vec<Dictionary>, String> {
-	let db = std::fs::read_dir("db").map_err(|_| format!("can't open {:?}/db directory", std::env::current_dir()))?;
-	let tipnumber = get_tip_block_number().map_err(|err| err.to_string())?;
+	let db = std::fs::read_dir(account::db_dir()).map_err(|_| format!("can't open {:?}/{} directory", std::env::current_dir(), account::db_dir()))?;
+	let tipnumber = rpc::get_tip_block_number()?;
 	let mut values = vec![];
 	for path in db {
 		let script_hash = {
-			let filename = path.map_err(|err| err.to_string())?.file_name();
-			let filename = String::from(filename.to_str().unwrap());
-			String::from(filename.strip_suffix(".json").unwrap())
-		};
-		let store = cache::recover(script_hash.clone())?;
-		if let Ok(hash) = hex::decode(script_hash.clone()) {
-			if hash[..] != store.script_hash[..] {
-				println!("skip unmatched cache file {}.json", script_hash);
+			let path = path.map_err(|err| err.to_string())?;
+			if !path.path().is_file() {
 				continue
 			}
-		} else {
-			println!("skip invalid cache file {}.json", script_hash);
+			let filename = String::from(path.file_name().to_str().unwrap());
+			match filename.strip_suffix(".json") {
+				Some(name) if cache::is_cache_name(name) => String::from(name),
+				_                                        => continue
+			}
+		};
+		let store = cache::recover(script_hash.clone())?;
+		let hash = hex::decode(script_hash.clone()).unwrap();
+		if hash[..] != store.script_hash[..] {
+			println!("skip unmatched cache file {}.json", script_hash);
 			continue
 		}
 		let lock_args = cache::get_kabletop_args()?;
-		let signed_rounds = cache::get_kabletop_signed_rounds()?;
 		let user1_pkhash: [u8; 20] = lock_args.user1_pkhash().into();
 		let user2_pkhash: [u8; 20] = lock_args.user2_pkhash().into();
-		let owner_pkhash = privkey_to_pkhash(&VARS.common.user_key.privkey);
+		let owner_pkhash = account::active_pubhash();
 		let challenge = Dictionary::new();
 		if owner_pkhash[..] == user1_pkhash[..] || owner_pkhash[..] == user2_pkhash[..] {
 			let user1_nfts = Vec::from(lock_args.user1_nfts()).iter().map(|nft| hex::encode(nft)).collect::<Vec<_>>();
@@ -328,9 +482,9 @@ pub fn scan_uncomplete_kabletop_cache() -> Result<Vec<Dictionary>, String> {
 		}
 		let round_count = {
 			if !store.round_operations.is_empty() {
-				signed_rounds.len() + 1
+				store.round_index
 			} else {
-				signed_rounds.len()
+				store.round_index - 1
 			}
 		};
 		let blocknumber: u64 = lock_args.begin_blocknumber().into();
@@ -372,7 +526,17 @@ pub fn scan_uncomplete_kabletop_cache() -> Result<Vec<Dictionary>, String> {
 					std::cmp::max(begin + count as u64 * 125 + round * 30, tip) - tip
 				});
 			} else {
-				remove_kabletop_cache(value.get("script_hash").to_string());
+				// the channel has been closed by other ways, so keep its rounds in archive store along with the real close
+				// transaction, but who won can't be told from it
+				if let Ok(store) = cache::recover(value.get("script_hash").to_string()) {
+					let archived = find_close_transaction(store.script_args).and_then(|close| match close {
+						Some((hash, timestamp)) => cache::archive_at(cache::UNKNOWN_WINNER, hash.0, timestamp),
+						None                    => cache::archive(cache::UNKNOWN_WINNER, [0u8; 32])
+					});
+					if let Err(err) = archived {
+						println!("archive kabletop cache error: {}", err);
+					}
+				}
 				return None
 			}
 			Some(value.into_shared())
@@ -389,7 +553,8 @@ pub fn replay_kabletop_cache(script_hash: String) -> Result<(), String> {
 	if let Err(error) = hash {
 		return Err(error.to_string())
 	}
-	let store = cache::recover(script_hash)?;
+	let store = cache::recover(script_hash.clone())
+		.or_else(|_| cache::recover_archived(script_hash))?;
 	let lock_args = cache::get_kabletop_args()?;
 	let signed_rounds = {
 		let mut cached_rounds = cache::get_kabletop_signed_rounds()?;
@@ -403,11 +568,34 @@ pub fn replay_kabletop_cache(script_hash: String) -> Result<(), String> {
 		}
 		cached_rounds
 	};
+	replay_signed_rounds(hash.unwrap().as_slice(), &lock_args, signed_rounds)?;
+	let user1_pkhash: [u8; 20] = lock_args.user1_pkhash().into();
+	let user2_pkhash: [u8; 20] = lock_args.user2_pkhash().into();
+	let owner_pkhash = account::active_pubhash();
+	if (owner_pkhash[..] == user1_pkhash[..] && store.user_type != 1)
+		|| (owner_pkhash[..] == user2_pkhash[..] && store.user_type != 2)
+		|| (owner_pkhash[..] != user1_pkhash[..] && owner_pkhash[..] != user2_pkhash[..]) {
+		return Err(String::from("owner pkhash dosen't match both of two users"))
+	}
+	Ok(())
+}
+
+pub fn replay_portable_file(path: String) -> Result<u8, String> {
+	let replay = cache::import_replay(path)?;
+	replay_signed_rounds(&replay.kabletop_script_hash(), &replay.kabletop_args()?, replay.kabletop_signed_rounds()?)?;
+	// the winner recorded in the file is not trusted, take the one that the replayed rounds end up with
+	match LUA.lock().unwrap().as_ref() {
+		Some(lua) => Ok(lua.winner()),
+		None      => Err(String::from("lua vm is not running"))
+	}
+}
+
+fn replay_signed_rounds(seed: &[u8], lock_args: &Args, signed_rounds: Vec<(Round, Signature)>) -> Result<(), String> {
 	let lua = Lua::new(0, 0);
 	lua.inject_nfts(from_nfts(lock_args.user1_nfts().into()), from_nfts(lock_args.user2_nfts().into()));
 	lua.boost(get_lua_entry());
 	set_lua(lua);
-	randomseed(hash.unwrap().as_slice());
+	randomseed(seed);
 	for (round, signature) in signed_rounds {
 		let operations: Vec<String> = {
 			let operations: Vec<Vec<u8>> = round.operations().into();
@@ -421,14 +609,6 @@ pub fn replay_kabletop_cache(script_hash: String) -> Result<(), String> {
 		}
 		randomseed(signature.serialize().as_slice());
 	}
-	let user1_pkhash: [u8; 20] = lock_args.user1_pkhash().into();
-	let user2_pkhash: [u8; 20] = lock_args.user2_pkhash().into();
-	let owner_pkhash = privkey_to_pkhash(&VARS.common.user_key.privkey);
-	if (owner_pkhash[..] == user1_pkhash[..] && store.user_type != 1)
-		|| (owner_pkhash[..] == user2_pkhash[..] && store.user_type != 2)
-		|| (owner_pkhash[..] != user1_pkhash[..] && owner_pkhash[..] != user2_pkhash[..]) {
-		return Err(String::from("owner pkhash dosen't match both of two users"))
-	}
 	Ok(())
 }
 
@@ -487,6 +667,14 @@ pub fn from_dictionary(value: Dictionary) -> Vec<String> {
 		.concat()
 }
 
+pub fn from_transfers_dictionary(value: Dictionary) -> Vec<(String, Vec<String>)> {
+	value
+		.iter()
+		.map(|(to, nfts)| (to.to_string(), from_dictionary(nfts.to_dictionary())))
+		.filter(|(_, nfts)| !nfts.is_empty())
+		.collect::<Vec<_>>()
+}
+
 pub fn init_panic_hook() {
     let old_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |panic_info| {
@@ -516,10 +704,10 @@ pub fn init_panic_hook() {
     }));
 }
 
-pub fn close_kabletop_channel() -> Result<[u8; 32], String> {
+pub fn close_kabletop_channel(fee_rate: Option<u64>) -> Result<[u8; 32], String> {
 	match *P2PMODE.lock().unwrap() {
-		P2pMode::Client => client::close_kabletop_channel(),
-		P2pMode::Server => server::close_kabletop_channel(),
+		P2pMode::Client => client::close_kabletop_channel(fee_rate),
+		P2pMode::Server => server::close_kabletop_channel(fee_rate),
 		P2pMode::Empty  => Err(String::from("empty mode"))
 	}
 }
@@ -558,6 +746,25 @@ pub fn notify_game_over() -> Result<[u8; 65], String> {
 	}
 }
 
+// trading waits for opposite to answer, so p2p mode isn't locked during it
+pub fn offer_trade(nfts: Vec<String>, price: u64) -> Result<[u8; 32], String> {
+	let mode = *P2PMODE.lock().unwrap();
+	match mode {
+		P2pMode::Client => client::offer_trade(nfts, price),
+		P2pMode::Server => server::offer_trade(nfts, price),
+		P2pMode::Empty  => Err(String::from("empty mode"))
+	}
+}
+
+pub fn propose_swap(gives: Vec<String>, takes: Vec<String>) -> Result<[u8; 32], String> {
+	let mode = *P2PMODE.lock().unwrap();
+	match mode {
+		P2pMode::Client => client::propose_swap(gives, takes),
+		P2pMode::Server => server::propose_swap(gives, takes),
+		P2pMode::Empty  => Err(String::from("empty mode"))
+	}
+}
+
 pub fn disconnect() -> Result<(), String> {
 	match *P2PMODE.lock().unwrap() {
 		P2pMode::Client => Ok(client::disconnect()),
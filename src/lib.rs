@@ -1,14 +1,14 @@
 use gdnative::prelude::*;
 use gdnative::api::*;
 use kabletop_godot_sdk::{
-	lua::highlevel::Lua, cache, ckb::*, USE_GODOT, p2p::{
+	lua::highlevel::Lua, cache, account, catalog, deck, ckb::*, USE_GODOT, p2p::{
 		client, server, protocol_relay::methods::reply::hook as relay_hook, protocol::{
 			methods::reply::hook, types::GodotType
 		}
 	}
 };
 use std::{
-	thread, collections::HashMap
+	thread, collections::HashMap, convert::TryInto
 };
 
 mod helper;
@@ -45,10 +45,46 @@ impl Kabletop {
 			push_event("channel_status", vec![true.to_variant(), hex::encode(hash).to_variant()]);
 			persist_kabletop_cache();
 		});
+		// both sides of a cooperative close archive the match here, and a closing from challenge is archived by its callback
 		hook::add("close_kabletop_channel", |hash| {
 			push_event("channel_status", vec![false.to_variant(), hex::encode(hash).to_variant()]);
-			remove_kabletop_cache(hex::encode(cache::get_clone().script_hash));
+			match hash.as_slice().try_into() {
+				Ok(close_hash) => {
+					archive_kabletop_cache(cache::get_clone().winner, close_hash);
+				},
+				Err(_) => godot_print!("can't archive kabletop cache with invalid close hash {}", hex::encode(hash))
+			}
+		});
+		hook::add("offer_trade", |offer| {
+			if let Ok(offer) = serde_json::from_slice::<TradeOffer>(offer) {
+				let mut nfts = HashMap::new();
+				for nft in offer.nfts {
+					*nfts.entry(nft).or_insert(0) += 1;
+				}
+				push_event("trade_offered", vec![
+					hex::encode(offer.seller).to_variant(), from_owned_nfts(&nfts).into_shared().to_variant(), offer.price.to_variant(),
+					offer.answer_id.to_variant()
+				]);
+			}
+		});
+		hook::add("propose_swap", |proposal| {
+			if let Ok(mut proposal) = serde_json::from_slice::<SwapProposal>(proposal) {
+				proposal.gives.sort();
+				proposal.takes.sort();
+				push_event("swap_proposed", vec![
+					hex::encode(proposal.proposer).to_variant(), into_dictionary(&proposal.gives).to_variant(), into_dictionary(&proposal.takes).to_variant(),
+					proposal.answer_id.to_variant()
+				]);
+			}
+		});
+		hook::add("submit_trade", |hash| {
+			refresh_chain_state();
+			push_event("trade_settled", vec![hex::encode(hash).to_variant()]);
+		});
+		set_tx_status_listener(|hash, status, confirmations| {
+			push_event("tx_status", vec![hex::encode(hash).to_variant(), status.name().to_variant(), confirmations.to_variant()]);
 		});
+		set_chain_event_listener(handle_chain_event);
 		relay_hook::add("propose_connection", |_| {
 			push_event("connect_status", vec!["PARTNER".to_variant(), true.to_variant()]);
 		});
@@ -115,6 +151,12 @@ impl Kabletop {
 					default: Dictionary::new_shared().to_variant(),
 					export_info: ExportInfo::new(VariantType::Dictionary),
 					usage: PropertyUsage::DEFAULT
+				},
+				SignalArgument {
+					name: "nft_infos",
+					default: Dictionary::new_shared().to_variant(),
+					export_info: ExportInfo::new(VariantType::Dictionary),
+					usage: PropertyUsage::DEFAULT
 				}
 			]
         });
@@ -122,16 +164,130 @@ impl Kabletop {
             name: "box_status_updated",
             args: &[
 				SignalArgument {
-					name: "box_count",
+					name: "status",
+					default: Dictionary::new_shared().to_variant(),
+					export_info: ExportInfo::new(VariantType::Dictionary),
+					usage: PropertyUsage::DEFAULT
+				}
+			]
+        });
+        builder.add_signal(Signal {
+            name: "capacity_updated",
+            args: &[
+				SignalArgument {
+					name: "capacity",
 					default: 0.to_variant(),
 					export_info: ExportInfo::new(VariantType::I64),
 					usage: PropertyUsage::DEFAULT
-				},
+				}
+			]
+        });
+        builder.add_signal(Signal {
+            name: "channel_cell_updated",
+            args: &[
 				SignalArgument {
-					name: "reveal_ready",
-					default: true.to_variant(),
+					name: "alive",
+					default: false.to_variant(),
 					export_info: ExportInfo::new(VariantType::Bool),
 					usage: PropertyUsage::DEFAULT
+				},
+				SignalArgument {
+					name: "challenger",
+					default: 0.to_variant(),
+					export_info: ExportInfo::new(VariantType::I64),
+					usage: PropertyUsage::DEFAULT
+				}
+			]
+        });
+        builder.add_signal(Signal {
+            name: "trade_offered",
+            args: &[
+				SignalArgument {
+					name: "seller",
+					default: "".to_variant(),
+					export_info: ExportInfo::new(VariantType::GodotString),
+					usage: PropertyUsage::DEFAULT
+				},
+				SignalArgument {
+					name: "nfts",
+					default: Dictionary::new_shared().to_variant(),
+					export_info: ExportInfo::new(VariantType::Dictionary),
+					usage: PropertyUsage::DEFAULT
+				},
+				SignalArgument {
+					name: "price",
+					default: 0.to_variant(),
+					export_info: ExportInfo::new(VariantType::I64),
+					usage: PropertyUsage::DEFAULT
+				},
+				SignalArgument {
+					name: "answer_id",
+					default: 0.to_variant(),
+					export_info: ExportInfo::new(VariantType::I64),
+					usage: PropertyUsage::DEFAULT
+				}
+			]
+        });
+        builder.add_signal(Signal {
+            name: "swap_proposed",
+            args: &[
+				SignalArgument {
+					name: "proposer",
+					default: "".to_variant(),
+					export_info: ExportInfo::new(VariantType::GodotString),
+					usage: PropertyUsage::DEFAULT
+				},
+				SignalArgument {
+					name: "gives",
+					default: Dictionary::new_shared().to_variant(),
+					export_info: ExportInfo::new(VariantType::Dictionary),
+					usage: PropertyUsage::DEFAULT
+				},
+				SignalArgument {
+					name: "takes",
+					default: Dictionary::new_shared().to_variant(),
+					export_info: ExportInfo::new(VariantType::Dictionary),
+					usage: PropertyUsage::DEFAULT
+				},
+				SignalArgument {
+					name: "answer_id",
+					default: 0.to_variant(),
+					export_info: ExportInfo::new(VariantType::I64),
+					usage: PropertyUsage::DEFAULT
+				}
+			]
+        });
+        builder.add_signal(Signal {
+            name: "trade_settled",
+            args: &[
+				SignalArgument {
+					name: "hash",
+					default: "".to_variant(),
+					export_info: ExportInfo::new(VariantType::GodotString),
+					usage: PropertyUsage::DEFAULT
+				}
+			]
+        });
+        builder.add_signal(Signal {
+            name: "tx_status",
+            args: &[
+				SignalArgument {
+					name: "hash",
+					default: "".to_variant(),
+					export_info: ExportInfo::new(VariantType::GodotString),
+					usage: PropertyUsage::DEFAULT
+				},
+				SignalArgument {
+					name: "status",
+					default: "".to_variant(),
+					export_info: ExportInfo::new(VariantType::GodotString),
+					usage: PropertyUsage::DEFAULT
+				},
+				SignalArgument {
+					name: "confirmations",
+					default: 0.to_variant(),
+					export_info: ExportInfo::new(VariantType::I64),
+					usage: PropertyUsage::DEFAULT
 				}
 			]
         });
@@ -158,12 +314,18 @@ impl Kabletop {
     fn _ready(&mut self, owner: TRef<Node>) {
         godot_print!("welcome to the kabletop world!");
 		set_godot_emitor(owner.claim());
-		// update_owned_nfts();
-		// update_box_status();
+		if let Err(err) = resume_transactions() {
+			godot_print!("resume_transactions error: {}", err);
+		}
+		// ask network of ckb node in advance to make address formatting not block
+		thread::spawn(|| if let Err(err) = network() {
+			godot_print!("network error: {}", err);
+		});
+		start_chain_watcher();
     }
 
 	#[export]
-	fn _process(&mut self, _owner: &Node, delta: f32) {
+	fn _process(&mut self, _owner: &Node, _delta: f32) {
 		if let Ok(mut events) = EVENTS.try_lock() {
 			if let Some(emitor) = get_godot_emitor() {
 				for (name, value) in &*events {
@@ -181,7 +343,6 @@ impl Kabletop {
 			}
 			(*funcrefs).clear();
 		}
-		process_delay_funcs(delta);
 	}
 
 	#[export]
@@ -195,8 +356,45 @@ impl Kabletop {
 	}
 
 	#[export]
-	fn get_ckb(&mut self, _owner: &Node) -> u64 {
-		online_capacity().unwrap()
+	fn get_ckb(&mut self, _owner: &Node) -> Variant {
+		match online_capacity() {
+			Ok(capacity) => capacity.to_variant(),
+			Err(err)     => err.to_variant()
+		}
+	}
+
+	// balance asks indexer for every cell of user, so it's reported to callback as [ok, balance or error]
+	#[export]
+	fn get_balance(&mut self, _owner: &Node, callback: Ref<FuncRef>) {
+		thread::spawn(move || match balance() {
+			Ok(balance) => FUNCREFS.lock().unwrap().push((callback, vec![true.to_variant(), from_balance(&balance).into_shared().to_variant()])),
+			Err(err)    => FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), err.to_variant()]))
+		});
+	}
+
+	#[export]
+	fn set_confirmation_depth(&mut self, _owner: &Node, depth: u64) {
+		set_confirmation_depth(depth);
+	}
+
+	#[export]
+	fn set_watch_interval(&mut self, _owner: &Node, interval: u64) {
+		set_watch_interval(interval);
+	}
+
+	// switch all chain reads and submissions to the in-process mock chain (or back to the configured node)
+	#[export]
+	fn use_mock_chain(&mut self, _owner: &Node, enabled: bool) -> Variant {
+		if let Err(err) = mock::set_mock_chain(enabled) {
+			return err.to_variant();
+		}
+		refresh_chain_state();
+		Variant::default()
+	}
+
+	#[export]
+	fn advance_mock_blocks(&mut self, _owner: &Node, count: u64) {
+		mock::advance_mock_blocks(count);
 	}
 
 	#[export]
@@ -208,6 +406,75 @@ impl Kabletop {
 			.concat();
 	}
 
+	#[export]
+	fn get_decks(&self, _owner: &Node) -> Variant {
+		match deck::list_decks() {
+			Ok(decks) => {
+				let value = Dictionary::new();
+				for (name, nfts) in decks {
+					value.insert(name, into_dictionary(&nfts));
+				}
+				value.into_shared().to_variant()
+			},
+			Err(err) => err.to_variant()
+		}
+	}
+
+	#[export]
+	fn save_deck(&self, _owner: &Node, name: String, nfts: Dictionary) -> Variant {
+		match deck::save_deck(name, from_dictionary(nfts)) {
+			Ok(_)    => Variant::default(),
+			Err(err) => err.to_variant()
+		}
+	}
+
+	#[export]
+	fn delete_deck(&self, _owner: &Node, name: String) -> Variant {
+		match deck::delete_deck(name) {
+			Ok(deleted) => deleted.to_variant(),
+			Err(err)    => err.to_variant()
+		}
+	}
+
+	#[export]
+	fn select_deck(&mut self, _owner: &Node, name: String, #[opt] callback: Option<Ref<FuncRef>>) -> Variant {
+		let nfts = match deck::load_deck(name) {
+			Ok(nfts) => nfts,
+			Err(err) => return err.to_variant()
+		};
+		self.nfts = nfts.clone();
+		// validation reaches rpc and lua, so the result is reported to callback as [ok, issues or error]
+		if let Some(callback) = callback {
+			thread::spawn(move || match deck::validate_deck(&nfts) {
+				Ok(issues) => FUNCREFS.lock().unwrap().push((callback, vec![issues.is_empty().to_variant(), issues.to_variant()])),
+				Err(err)   => FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), err.to_variant()]))
+			});
+		}
+		Variant::default()
+	}
+
+	#[export]
+	fn validate_deck(&self, _owner: &Node, nfts: Dictionary, callback: Ref<FuncRef>) {
+		let nfts = from_dictionary(nfts);
+		thread::spawn(move || match deck::validate_deck(&nfts) {
+			Ok(issues) => FUNCREFS.lock().unwrap().push((callback, vec![true.to_variant(), issues.to_variant()])),
+			Err(err)   => FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), err.to_variant()]))
+		});
+	}
+
+	#[export]
+	fn set_deck_rules(&self, _owner: &Node, path: String) -> Variant {
+		let path = if path.is_empty() {
+			None
+		} else {
+			Some(path)
+		};
+		match deck::set_deck_rules(path) {
+			Ok(_)    => Variant::default(),
+			Err(err) => err.to_variant()
+		}
+	}
+
 	#[export]
 	fn get_selected_nfts(&self, _owner: &Node) -> Dictionary {
 		into_dictionary(&self.nfts)
@@ -230,45 +497,152 @@ impl Kabletop {
 	}
 
 	#[export]
-	fn delete_nfts(&mut self, _owner: &Node, nfts: Dictionary, callback: Ref<FuncRef>) {
+	fn delete_nfts(&mut self, _owner: &Node, nfts: Dictionary, callback: Ref<FuncRef>, #[opt] fee_rate: Option<u64>) {
 		let nfts = from_dictionary(nfts);
 		if !nfts.is_empty() {
-			discard_nfts(nfts, handle_transaction(update_owned_nfts, callback));
+			discard_nfts(nfts, fee_rate, handle_transaction(refresh_chain_state, callback));
 		}
 	}
 
 	#[export]
-	fn transfer_nfts(&self, _owner: &Node, nfts: Dictionary, to: String, callback: Ref<FuncRef>) {
+	fn transfer_nfts(&self, _owner: &Node, nfts: Dictionary, to: String, callback: Ref<FuncRef>, #[opt] fee_rate: Option<u64>) {
 		let nfts = from_dictionary(nfts);
 		if !nfts.is_empty() {
-			transfer_nfts(nfts, to, handle_transaction(update_owned_nfts, callback));
+			transfer_nfts(nfts, to, fee_rate, handle_transaction(refresh_chain_state, callback));
+		}
+	}
+
+	#[export]
+	fn transfer_nfts_batch(&self, _owner: &Node, transfers: Dictionary, callback: Ref<FuncRef>, #[opt] fee_rate: Option<u64>) {
+		let transfers = from_transfers_dictionary(transfers);
+		if !transfers.is_empty() {
+			transfer_nfts_batch(transfers, fee_rate, handle_transaction(refresh_chain_state, callback));
+		}
+	}
+
+	#[export]
+	fn preview_consolidation(&self, _owner: &Node, #[opt] fee_rate: Option<u64>) -> Variant {
+		match preview_consolidation(fee_rate) {
+			Ok(preview) => from_consolidation_preview(&preview).into_shared().to_variant(),
+			Err(err)    => err.to_variant()
 		}
 	}
 
 	#[export]
-	fn issue_nfts(&self, _owner: &Node, nfts: Dictionary, callback: Ref<FuncRef>) {
+	fn consolidate_cells(&self, _owner: &Node, callback: Ref<FuncRef>, #[opt] fee_rate: Option<u64>) {
+		consolidate_cells(fee_rate, move |result: Result<Vec<H256>, String>| {
+			match result {
+				Ok(hashes) => {
+					refresh_chain_state();
+					let hashes = hashes.into_iter().map(|hash| hex::encode(hash)).collect::<Vec<_>>();
+					FUNCREFS.lock().unwrap().push((callback.clone(), vec![true.to_variant(), hashes.to_variant()]));
+				},
+				Err(err) => {
+					FUNCREFS.lock().unwrap().push((callback.clone(), vec![false.to_variant(), err.to_variant()]));
+				}
+			}
+		});
+	}
+
+	#[export]
+	fn issue_nfts(&self, _owner: &Node, nfts: Dictionary, callback: Ref<FuncRef>, #[opt] fee_rate: Option<u64>) {
 		let nfts = from_dictionary(nfts);
 		if !nfts.is_empty() {
-			issue_nfts(nfts, handle_transaction(update_owned_nfts, callback));
+			issue_nfts(nfts, fee_rate, handle_transaction(refresh_chain_state, callback));
 		}
 	}
 
 	#[export]
-	fn purchase_nfts(&self, _owner: &Node, count: u8, callback: Ref<FuncRef>) {
-		purchase_nfts(count, handle_transaction(update_box_status, callback));
+	fn purchase_nfts(&self, _owner: &Node, count: u8, callback: Ref<FuncRef>, #[opt] fee_rate: Option<u64>) {
+		purchase_nfts(count, fee_rate, handle_transaction(refresh_chain_state, callback));
 	}
 
 	#[export]
-	fn reveal_nfts(&self, _owner: &Node, callback: Ref<FuncRef>) {
-		reveal_nfts(handle_transaction(|| {
-			update_box_status();
-			update_owned_nfts();
-		}, callback));
+	fn reveal_nfts(&self, _owner: &Node, callback: Ref<FuncRef>, #[opt] fee_rate: Option<u64>) {
+		reveal_nfts(fee_rate, handle_transaction(refresh_chain_state, callback));
 	}
 
 	#[export]
-	fn create_nft_wallet(&self, _owner: &Node, callback: Ref<FuncRef>) {
-		create_wallet(handle_transaction(update_box_status, callback));
+	fn create_nft_wallet(&self, _owner: &Node, callback: Ref<FuncRef>, #[opt] fee_rate: Option<u64>) {
+		create_wallet(fee_rate, handle_transaction(refresh_chain_state, callback));
+	}
+
+	#[export]
+	fn estimate_fee(&self, _owner: &Node, action: String, params: Dictionary, callback: Ref<FuncRef>) {
+		let fee_rate = if params.contains("fee_rate") {
+			Some(params.get("fee_rate").to_u64())
+		} else {
+			None
+		};
+		let action = match into_wallet_action(action, params) {
+			Ok(action) => action,
+			Err(err)   => {
+				FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), err.to_variant()]));
+				return
+			}
+		};
+		thread::spawn(move || match estimate_fee(action, fee_rate) {
+			Ok(fee)    => FUNCREFS.lock().unwrap().push((callback, vec![true.to_variant(), fee.to_variant()])),
+			Err(error) => FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), error.to_variant()]))
+		});
+	}
+
+	#[export]
+	fn build_transaction(&self, _owner: &Node, action: String, params: Dictionary, callback: Ref<FuncRef>) {
+		let fee_rate = if params.contains("fee_rate") {
+			Some(params.get("fee_rate").to_u64())
+		} else {
+			None
+		};
+		let action = match into_wallet_action(action, params) {
+			Ok(action) => action,
+			Err(err)   => {
+				FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), err.to_variant()]));
+				return
+			}
+		};
+		thread::spawn(move || match build_transaction(action, fee_rate) {
+			Ok((handle, summary)) => {
+				let summary = from_transaction_summary(summary);
+				summary.insert("handle", handle);
+				FUNCREFS.lock().unwrap().push((callback, vec![true.to_variant(), summary.into_shared().to_variant()]));
+			},
+			Err(error) => FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), error.to_variant()]))
+		});
+	}
+
+	#[export]
+	fn sign_and_submit(&self, _owner: &Node, handle: u64, callback: Ref<FuncRef>) {
+		sign_and_submit(handle, handle_transaction(refresh_chain_state, callback));
+	}
+
+	#[export]
+	fn cancel_transaction(&self, _owner: &Node, handle: u64) -> bool {
+		cancel_transaction(handle)
+	}
+
+	#[export]
+	fn get_transaction_history(&self, _owner: &Node, limit: u64, cursor: String, callback: Ref<FuncRef>) {
+		thread::spawn(move || match get_transaction_history(limit as usize, cursor) {
+			Ok(history) => FUNCREFS.lock().unwrap().push((callback, vec![true.to_variant(), history.to_variant()])),
+			Err(error)  => FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), error.to_variant()]))
+		});
+	}
+
+	#[export]
+	fn get_my_address(&self, _owner: &Node) -> Option<String> {
+		// network is resolved in _ready, so main thread never waits for ckb node here
+		if resolved_network().is_none() {
+			godot_print!("get_my_address error: network of ckb node isn't resolved yet");
+			return None
+		}
+		match my_address() {
+			Ok(address) => Some(address),
+			Err(err)    => {
+				godot_print!("get_my_address error: {}", err);
+				None
+			}
+		}
 	}
 
 	#[export]
@@ -276,23 +650,55 @@ impl Kabletop {
 		if from_cache {
 			(*NFTS.lock().unwrap()).clone()
 		} else {
-			update_owned_nfts();
+			refresh_chain_state();
 			None
 		}
 	}
 
+	// fetch all owned nfts page by page, progress receives nfts of every page and the count of nft cells fetched so far
 	#[export]
-	fn get_box_status(&self, _owner: &Node) -> Option<Dictionary> {
-		if let Some(status) = *STATUS.lock().unwrap() {
-			let map = Dictionary::new();
-			map.insert("count", status.0.to_variant());
-			map.insert("ready", status.1.to_variant());
-			Some(map.into_shared())
-		} else {
-			None
+	fn fetch_owned_nfts(&self, _owner: &Node, callback: Ref<FuncRef>, #[opt] progress: Option<Ref<FuncRef>>) {
+		thread::spawn(move || {
+			let result = owned_nfts_with_progress(|nfts, cells| {
+				if let Some(progress) = &progress {
+					let nfts = from_owned_nfts(nfts).into_shared().to_variant();
+					FUNCREFS.lock().unwrap().push((progress.clone(), vec![nfts, (cells as u64).to_variant()]));
+				}
+			});
+			match result {
+				Ok(nfts) => {
+					let nfts = from_owned_nfts(&nfts).into_shared().to_variant();
+					FUNCREFS.lock().unwrap().push((callback, vec![true.to_variant(), nfts]));
+				},
+				Err(err) => {
+					FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), err.to_variant()]));
+				}
+			}
+		});
+	}
+
+	#[export]
+	fn get_nft_info(&self, _owner: &Node, hash: String) -> Option<Dictionary> {
+		catalog::nft_info(hash.as_str()).map(from_nft_info)
+	}
+
+	#[export]
+	fn load_nft_catalog(&self, _owner: &Node, path: String) -> Variant {
+		match catalog::load_catalog(path) {
+			Ok(count) => count.to_variant(),
+			Err(err)  => err.to_variant()
 		}
 	}
 
+	#[export]
+	fn get_box_status(&self, _owner: &Node) -> Option<Dictionary> {
+		STATUS
+			.lock()
+			.unwrap()
+			.as_ref()
+			.map(|status| from_wallet_status(status).into_shared())
+	}
+
 	#[export]
 	fn get_cache(&self, _owner: &Node) -> Dictionary {
 		let clone = cache::get_clone();
@@ -310,6 +716,8 @@ impl Kabletop {
 		value.insert("winner", clone.winner);
 		value.insert("user_type", clone.user_type);
 		value.insert("opponent_type", clone.opponent_type);
+		value.insert("round_index", clone.round_index);
+		value.insert("round_owner", clone.round_owner);
 		value.insert("round_operations", clone.round_operations);
 		value.into_shared()
 	}
@@ -329,12 +737,11 @@ impl Kabletop {
 	}
 
 	#[export]
-	fn listen_at(&self, _owner: &Node, socket: String, staking_ckb: u64, bet_ckb: u64) -> Variant {
-		if self.nfts.len() == 0 {
-			return "empty nfts".to_variant();
-		}
+	fn listen_at(&self, _owner: &Node, socket: String, staking_ckb: u64, bet_ckb: u64, #[opt] fee_rate: Option<u64>) -> Variant {
+		// deck is validated when client asks for opening channel
 		cache::init(cache::PLAYER_TYPE::TWO);
 		cache::set_staking_and_bet_ckb(staking_ckb, bet_ckb);
+		cache::set_fee_rate(fee_rate);
 		cache::set_playing_nfts(into_nfts(self.nfts.clone()));
 		let result = server::listen(socket.as_str(), move |id, connected| {
 			if connected {
@@ -358,17 +765,18 @@ impl Kabletop {
 	}
 
 	#[export]
-	fn create_channel(&self, _owner: &Node, staking_ckb: u64, bet_ckb: u64, callback: Ref<FuncRef>) {
-		if self.nfts.len() == 0 {
-			FUNCREFS.lock().unwrap().push((callback.clone(), vec![false.to_variant(), "empty nfts".to_variant()]));
-		}
+	fn create_channel(&self, _owner: &Node, staking_ckb: u64, bet_ckb: u64, callback: Ref<FuncRef>, #[opt] fee_rate: Option<u64>) {
 		if get_p2p_mode() != P2pMode::Client {
 			FUNCREFS.lock().unwrap().push((callback.clone(), vec![false.to_variant(), "no client mode".to_variant()]));
+			return
 		}
 		cache::init(cache::PLAYER_TYPE::ONE);
 		cache::set_staking_and_bet_ckb(staking_ckb, bet_ckb);
+		cache::set_fee_rate(fee_rate);
 		cache::set_playing_nfts(into_nfts(self.nfts.clone()));
-		thread::spawn(move || match client::open_kabletop_channel() {
+		let staking_ckb = cache::get_clone().staking_ckb;
+		let nfts = self.nfts.clone();
+		thread::spawn(move || match check_deck(&nfts).and_then(|_| check_channel_balance(staking_ckb)).and_then(|_| client::open_kabletop_channel()) {
 			Ok(hash) => {
 				// create lua vm
 				let clone = cache::get_clone();
@@ -389,7 +797,7 @@ impl Kabletop {
 	}
 
 	#[export]
-	fn close_channel(&self, _owner: &Node, from_challenge: bool, script_hash: Option<String>, callback: Ref<FuncRef>) {
+	fn close_channel(&self, _owner: &Node, from_challenge: bool, script_hash: Option<String>, callback: Ref<FuncRef>, #[opt] fee_rate: Option<u64>) {
 		let store = match script_hash {
 			Some(hash) => match cache::recover(hash) {
 				Ok(value) => value,
@@ -413,16 +821,18 @@ impl Kabletop {
 						return
 					}
 				};
-				let script_hash = store.script_hash;
+				let handle = handle_transaction(|| {}, callback);
 				close_challenged_kabletop_channel(
-					store.script_args, winner, from_challenge, signed_rounds, handle_transaction(move || {
-						remove_kabletop_cache(hex::encode(script_hash));
-					}, callback)
+					store.script_args, winner, from_challenge, signed_rounds, fee_rate, move |result: Result<H256, String>| {
+						if let Ok(hash) = &result {
+							archive_kabletop_cache(winner, hash.0);
+						}
+						handle(result);
+					}
 				)
 			} else {
-				match close_kabletop_channel() {
+				match close_kabletop_channel(fee_rate) {
 					Ok(hash) => {
-						remove_kabletop_cache(hex::encode(store.script_hash));
 						FUNCREFS.lock().unwrap().push((callback, vec![true.to_variant(), hex::encode(hash).to_variant()]));
 					},
 					Err(err) => {
@@ -434,7 +844,7 @@ impl Kabletop {
 	}
 
 	#[export]
-	fn challenge_channel(&self, _: &Node, script_hash: String, callback: Ref<FuncRef>) {
+	fn challenge_channel(&self, _: &Node, script_hash: String, callback: Ref<FuncRef>, #[opt] fee_rate: Option<u64>) {
 		persist_kabletop_cache();
 		match cache::recover(script_hash.clone()) {
 			Ok(store) => {
@@ -446,10 +856,14 @@ impl Kabletop {
 					}
 				};
 				challenge_kabletop_channel(
-					store.script_args, store.user_type, dump_cached_codes(false), signed_rounds, handle_transaction(|| {
+					store.script_args, store.user_type, dump_cached_codes(false), signed_rounds, fee_rate, handle_transaction(|| {
 						dump_cached_codes(true)
 							.into_iter()
-							.for_each(|code| cache::commit_user_operation(code));
+							.for_each(|code| {
+								if let Err(err) = cache::commit_user_operation(code) {
+									godot_print!("commit challenge operation error: {}", err);
+								}
+							});
 						persist_kabletop_cache();
 						remove_cached_codes();
 					}, callback)
@@ -473,6 +887,159 @@ impl Kabletop {
 		});
 	}
 
+	#[export]
+	fn verify_cache(&self, _owner: &Node, script_hash: String, callback: Ref<FuncRef>) {
+		thread::spawn(move || match verify_kabletop_cache(script_hash) {
+			Ok(report) => {
+				FUNCREFS.lock().unwrap().push((callback, vec![true.to_variant(), report.to_variant()]));
+			},
+			Err(error) => {
+				FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), error.to_variant()]));
+			}
+		});
+	}
+
+	#[export]
+	fn backup_caches(&self, _owner: &Node, path: String, password: String, callback: Ref<FuncRef>) {
+		// scrypt takes seconds, so keep it away from the main thread
+		thread::spawn(move || {
+			let value = match cache::backup(path, password) {
+				Ok(count) => vec![true.to_variant(), count.to_variant()],
+				Err(err)  => vec![false.to_variant(), err.to_variant()]
+			};
+			FUNCREFS.lock().unwrap().push((callback, value));
+		});
+	}
+
+	#[export]
+	fn restore_caches(&self, _owner: &Node, path: String, password: String, callback: Ref<FuncRef>) {
+		thread::spawn(move || {
+			let value = match cache::restore(path, password) {
+				Ok(report) => {
+					let value = Dictionary::new();
+					let rejected = Dictionary::new();
+					for (hash, issues) in report.rejected {
+						rejected.insert(hex::encode(hash), issues);
+					}
+					value.insert("restored", report.restored.iter().map(|hash| hex::encode(hash)).collect::<Vec<_>>());
+					value.insert("skipped", report.skipped.iter().map(|hash| hex::encode(hash)).collect::<Vec<_>>());
+					value.insert("rejected", rejected.into_shared());
+					vec![true.to_variant(), value.into_shared().to_variant()]
+				},
+				Err(err) => vec![false.to_variant(), err.to_variant()]
+			};
+			FUNCREFS.lock().unwrap().push((callback, value));
+		});
+	}
+
+	#[export]
+	fn list_accounts(&self, _owner: &Node) -> Variant {
+		match account::list_accounts() {
+			Ok(accounts) => accounts
+				.into_iter()
+				.map(|account| {
+					let value = Dictionary::new();
+					value.insert("name", account.name);
+					value.insert("pkhash", hex::encode(account.pkhash));
+					value.insert("active", account.active);
+					value.into_shared()
+				})
+				.collect::<Vec<_>>()
+				.to_variant(),
+			Err(err) => err.to_variant()
+		}
+	}
+
+	#[export]
+	fn generate_account(&self, _owner: &Node, name: String, password: String) -> Variant {
+		match account::generate_account(name, password.as_str()) {
+			Ok((pkhash, mnemonic)) => {
+				let value = Dictionary::new();
+				value.insert("pkhash", hex::encode(pkhash));
+				value.insert("mnemonic", mnemonic);
+				value.into_shared().to_variant()
+			},
+			Err(err) => err.to_variant()
+		}
+	}
+
+	#[export]
+	fn import_account(&self, _owner: &Node, name: String, secret: String, password: String) -> Variant {
+		match account::import_account(name, secret.as_str(), password.as_str()) {
+			Ok(pkhash) => hex::encode(pkhash).to_variant(),
+			Err(err)   => err.to_variant()
+		}
+	}
+
+	#[export]
+	fn switch_account(&self, _owner: &Node, pkhash: String, password: String) -> Variant {
+		if get_p2p_mode() != P2pMode::Empty {
+			return "can't switch account while p2p connection is alive".to_variant();
+		}
+		let pkhash = if pkhash.is_empty() {
+			None
+		} else {
+			match parse_recipient(pkhash.as_str()) {
+				Ok(pkhash) => Some(pkhash),
+				Err(err)   => return err.to_variant()
+			}
+		};
+		match account::switch_account(pkhash, password.as_str()) {
+			Ok(_) => {
+				refresh_chain_state();
+				Variant::default()
+			},
+			Err(err) => err.to_variant()
+		}
+	}
+
+	#[export]
+	fn use_external_signer(&self, _owner: &Node, address: String, token: String) -> Variant {
+		if get_p2p_mode() != P2pMode::Empty {
+			return "can't switch account while p2p connection is alive".to_variant();
+		}
+		match account::use_external_signer(address.as_str(), token.as_str()) {
+			Ok(_) => {
+				refresh_chain_state();
+				Variant::default()
+			},
+			Err(err) => err.to_variant()
+		}
+	}
+
+	#[export]
+	fn request_auto_approval(&self, _owner: &Node, channel: String, max_staking_ckb: u64, callback: Ref<FuncRef>) {
+		thread::spawn(move || {
+			let channel = if channel.is_empty() {
+				Ok(None)
+			} else {
+				let mut script_hash = [0u8; 32];
+				hex::decode_to_slice(channel.trim_start_matches("0x"), &mut script_hash)
+					.map(|_| Some(script_hash))
+					.map_err(|err| err.to_string())
+			};
+			let result = channel.and_then(|channel| {
+				account::active_signer().request_auto_approval(ApprovalPolicy {
+					channel,
+					max_staking_ckb
+				})
+			});
+			match result {
+				Ok(_)    => FUNCREFS.lock().unwrap().push((callback, vec![true.to_variant(), Variant::default()])),
+				Err(err) => FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), err.to_variant()]))
+			}
+		});
+	}
+
+	#[export]
+	fn get_match_history(&self, _owner: &Node, filter: Dictionary) -> Variant {
+		let history = into_match_filter(filter).and_then(get_match_history);
+		match history {
+			Ok(records) => records.to_variant(),
+			Err(err)    => err.to_variant()
+		}
+	}
+
 	#[export]
 	fn close_game(&self, _owner: &Node, from_challenge: bool, callback: Ref<FuncRef>) {
 		let store = cache::get_clone();
@@ -535,6 +1102,22 @@ impl Kabletop {
 		}
 	}
 
+	#[export]
+	fn export_replay(&self, _owner: &Node, script_hash: String, path: String) -> Variant {
+		match cache::export_replay(script_hash, path) {
+			Ok(_)    => Variant::default(),
+			Err(err) => err.to_variant()
+		}
+	}
+
+	#[export]
+	fn import_replay(&self, _owner: &Node, path: String) -> Variant {
+		match replay_portable_file(path) {
+			Ok(winner) => winner.to_variant(),
+			Err(err)   => err.to_variant()
+		}
+	}
+
 	#[export]
 	fn reply_p2p_message(&self, _owner: &Node, message: String, callback: Ref<FuncRef>) {
 		let name = message.clone();
@@ -613,7 +1196,48 @@ impl Kabletop {
 	}
 
 	#[export]
-	fn register_relay(&self, _owner: &Node, nickname: String, staking_ckb: u64, bet_ckb: u64) -> Variant {
+	fn sell_nfts(&self, _owner: &Node, nfts: Dictionary, price: u64, callback: Ref<FuncRef>) {
+		let nfts = from_dictionary(nfts);
+		thread::spawn(move || {
+			match offer_trade(nfts, price) {
+				Ok(hash) => {
+					refresh_chain_state();
+					FUNCREFS.lock().unwrap().push((callback, vec![true.to_variant(), hex::encode(hash).to_variant()]));
+				},
+				Err(err) => {
+					FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), err.to_variant()]));
+				}
+			}
+		});
+	}
+
+	#[export]
+	fn propose_swap(&self, _owner: &Node, my_nfts: Dictionary, their_nfts: Dictionary, callback: Ref<FuncRef>) {
+		let (gives, takes) = (from_dictionary(my_nfts), from_dictionary(their_nfts));
+		thread::spawn(move || {
+			match propose_swap(gives, takes) {
+				Ok(hash) => {
+					refresh_chain_state();
+					FUNCREFS.lock().unwrap().push((callback, vec![true.to_variant(), hex::encode(hash).to_variant()]));
+				},
+				Err(err) => {
+					FUNCREFS.lock().unwrap().push((callback, vec![false.to_variant(), err.to_variant()]));
+				}
+			}
+		});
+	}
+
+	// answer_id comes along with trade_offered or swap_proposed event
+	#[export]
+	fn answer_trade_offer(&self, _owner: &Node, answer_id: u64, accept: bool) -> Variant {
+		match answer_trade_offer(answer_id, accept) {
+			Ok(_)    => Variant::default(),
+			Err(err) => err.to_variant()
+		}
+	}
+
+	#[export]
+	fn register_relay(&self, _owner: &Node, nickname: String, staking_ckb: u64, bet_ckb: u64, #[opt] fee_rate: Option<u64>) -> Variant {
 		if self.nfts.len() == 0 {
 			return "empty nfts".to_variant();
 		}
@@ -622,6 +1246,7 @@ impl Kabletop {
 		} else {
 			cache::init(cache::PLAYER_TYPE::TWO);
 			cache::set_staking_and_bet_ckb(staking_ckb, bet_ckb);
+			cache::set_fee_rate(fee_rate);
 			cache::set_playing_nfts(into_nfts(self.nfts.clone()));
 			Variant::default()
 		}